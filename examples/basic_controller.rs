@@ -23,15 +23,28 @@ fn main() {
 
     let server = server_builder
         .configure_router(|router| {
-            let basic_test_cont = BasicController::new("/test", TestControllerContext::new("this is a private resource"));
-
-            basic_test_cont.add(Method::GET, "/", TestControllerContext::function_to_receive_any_get_http_call);
-
-            basic_test_cont.add(Method::POST, "/", |_, _, _| { println!("this was a post request") });
-
-            basic_test_cont.add_with_guards(Method::PUT, "/with-body", BodyGuard.into(), |_, _, _| { println!("this is only reachable if the request has a body") });
+            let basic_test_cont = BasicController::builder("/test", TestControllerContext::new("this is a private resource"))
+                .add(Method::GET, "/", TestControllerContext::function_to_receive_any_get_http_call)
+                .add(Method::POST, "/", |_, _, _| { println!("this was a post request") })
+                .add_with_guards(Method::PUT, "/with-body", BodyGuard.into(), |_, _, _| { println!("this is only reachable if the request has a body") })
+                .build()
+                .expect("Invalid controller routes");
+
+            let users_v1 = BasicController::builder("/users", ())
+                .add(Method::GET, "/<id>", |_, req, res| { res.status(StatusCode::OK).body(format!("v1 user {}", req.captures().get("id").unwrap())); })
+                .build()
+                .expect("Invalid controller routes");
+
+            let users_v2 = BasicController::builder("/users", ())
+                .add(Method::GET, "/<id>", |_, req, res| { res.status(StatusCode::OK).body(format!("{{\"id\":\"{}\"}}", req.captures().get("id").unwrap())); })
+                .build()
+                .expect("Invalid controller routes");
 
             router.add(basic_test_cont)
+                .version("v1", |router| router.add(users_v1))
+                .version("v2", |router| router.add(users_v2))
+                .default_version("v2")
+                .deprecate_version("v1")
         })
         .configure_listener(|listener_config| {
             listener_config.set_uri("http://0.0.0.0:12345")
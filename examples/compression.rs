@@ -0,0 +1,43 @@
+extern crate saphir;
+
+use saphir::*;
+use saphir::compression::ContentCoding;
+
+fn main() {
+    let server_builder = Server::builder();
+
+    let server = server_builder
+        .configure_router(|router| {
+            let report_cont = BasicController::builder("/report", ())
+                .add(Method::GET, "/", |_, _, res| {
+                    let rows: Vec<String> = (0..1000).map(|i| format!("{{\"id\":{},\"status\":\"ok\"}}", i)).collect();
+                    res.status(StatusCode::OK)
+                        .header(header::CONTENT_TYPE, "application/json")
+                        .body(format!("[{}]", rows.join(",")));
+                })
+                .add(Method::GET, "/small", |_, _, res| {
+                    res.status(StatusCode::OK).header(header::CONTENT_TYPE, "application/json").body("[]");
+                })
+                .add(Method::POST, "/", |_, req, res| {
+                    res.status(StatusCode::OK).body(format!("received {} bytes", req.body().len()));
+                })
+                .build()
+                .expect("Invalid controller routes");
+
+            router.add(report_cont)
+        })
+        .configure_listener(|listener_config| {
+            listener_config.set_uri("http://0.0.0.0:12345")
+        })
+        // Json responses of at least 512 bytes are compressed with gzip, or brotli when the client prefers it
+        .compression(Compression::new().min_size(512).codings(&[ContentCoding::Gzip, ContentCoding::Brotli]))
+        // Compressed request bodies are decoded, and rejected once they expand beyond 1 MiB
+        .decompress_request_bodies()
+        .max_body_size(1024 * 1024)
+        .build();
+
+    if let Err(e) = server.run() {
+        println!("{:?}", e);
+        assert!(false);
+    }
+}
@@ -4,6 +4,10 @@ use saphir::*;
 
 struct QueryParams(Vec<(String, String)>);
 
+struct ServerConfig {
+    name: String,
+}
+
 struct TestMiddleware {}
 
 impl Middleware for TestMiddleware {
@@ -17,7 +21,7 @@ impl Middleware for TestMiddleware {
             vec![]
         };
 
-        request.extensions_mut().insert(QueryParams(params));
+        request.insert_state(QueryParams(params));
 
         RequestContinuation::Continue
     }
@@ -43,44 +47,92 @@ fn main() {
     let server_builder = Server::builder();
 
     let server = server_builder
+        .with_state(ServerConfig { name: "saphir".to_string() })
+        .cookie_keys(CookieKeys::generate())
         .configure_middlewares(|stack| {
             stack.apply(TestMiddleware {}, vec!("/"), None)
         })
         .configure_router(|router| {
-            let basic_test_cont = BasicController::new("/tester", TestControllerContext::new("this is a private resource"));
-
-            basic_test_cont.add(Method::GET, "/", TestControllerContext::function_to_receive_any_get_http_call);
-
-            basic_test_cont.add(Method::POST, "/", |_, _, _| { println!("this was a post request") });
-
-            basic_test_cont.add(Method::GET, "/panic", |_, _, _| { panic!("lol") });
-
-            basic_test_cont.add(Method::GET, "/timeout", |_, _, _| { std::thread::sleep(std::time::Duration::from_millis(15000)) });
-
-            basic_test_cont.add(Method::GET, "/query", |_, req, _| {
-                if let Some(query_params) = req.extensions().get::<QueryParams>() {
-                    for param in &query_params.0 {
-                        println!("{:?}", param);
+            let basic_test_cont = BasicController::builder("/tester", TestControllerContext::new("this is a private resource"))
+                .add(Method::GET, "/", TestControllerContext::function_to_receive_any_get_http_call)
+                .add(Method::POST, "/", |_, _, _| { println!("this was a post request") })
+                .add(Method::GET, "/panic", |_, _, _| { panic!("lol") })
+                .add(Method::GET, "/timeout", |_, _, _| { std::thread::sleep(std::time::Duration::from_millis(15000)) })
+                .add(Method::GET, "/query", |_, req, _| {
+                    if let Some(query_params) = req.state::<QueryParams>() {
+                        for param in &query_params.0 {
+                            println!("{:?}", param);
+                        }
                     }
-                }
-            });
-
-            basic_test_cont.add_with_guards(Method::PUT, "/patate", BodyGuard.into(), |_, _, _| { println!("this is only reachable if the request has a body") });
-
-            let basic_test_cont2 = BasicController::new("/test2", TestControllerContext::new("this is a second private resource"));
-            basic_test_cont2.add(Method::GET, "/", |_, _, _| { println!("this was a get request handled by the second controller") });
+                })
+                .add_with_guards(Method::GET, "/query-required", StateGuard::<QueryParams>::new().into(), |_, req, _| {
+                    if let Ok(query_params) = req.require_state::<QueryParams>() {
+                        println!("{} query params", query_params.0.len());
+                    }
+                })
+                .add(Method::GET, "/name", |_, req, res| {
+                    if let Ok(config) = req.require_app_state::<ServerConfig>() {
+                        res.status(StatusCode::OK).body(config.name.clone());
+                    }
+                })
+                .add(Method::GET, "/visits", |_, req, res| {
+                    let visits = req.cookies().value::<u32>("visits").unwrap_or(0) + 1;
+                    res.status(StatusCode::OK)
+                        .cookie(Cookie::build("visits", visits.to_string()).path("/").http_only(true).finish())
+                        .body(format!("visit #{}", visits));
+                })
+                .add(Method::POST, "/login", |_, req, res| {
+                    let user = String::from_utf8_lossy(req.body()).to_string();
+                    let cookie = Cookie::build("user", user).path("/").http_only(true).same_site(SameSite::Strict).finish();
+                    if res.private_cookie(cookie).is_ok() {
+                        res.status(StatusCode::NO_CONTENT);
+                    }
+                })
+                .add(Method::GET, "/whoami", |_, req, res| {
+                    match req.cookies().get_private("user") {
+                        Some(user) => res.status(StatusCode::OK).body(user.value().to_string()),
+                        None => res.status(StatusCode::UNAUTHORIZED),
+                    };
+                })
+                .add(Method::POST, "/logout", |_, _, res| {
+                    res.status(StatusCode::NO_CONTENT).remove_cookie(Cookie::build("user", "").path("/").finish());
+                })
+                .add_with_guards(Method::PUT, "/patate", BodyGuard.into(), |_, _, _| { println!("this is only reachable if the request has a body") })
+                .build()
+                .expect("Invalid controller routes");
+
+            let basic_test_cont2 = BasicController::builder("/test2", TestControllerContext::new("this is a second private resource"))
+                .add(Method::GET, "/", |_, _, _| { println!("this was a get request handled by the second controller") })
+                .build()
+                .expect("Invalid controller routes");
 
             // This will add the controller and so the following method+route will be valid
             // GET  /test/
             // POST /test/
             // GET  /test/query
             // PUT  /test/patate
+            // GET  /test/visits
+            // POST /test/login
+            // GET  /test/whoami
+            // POST /test/logout
 
             // This will add the controller at the specified route and so the following method+route will be valid
             // GET  /api/test2/
 
+            // This will serve the following method+route on any subdomain of localhost, e.g. `acme.localhost`
+            // GET  /tenant/
+            let tenant_cont = BasicController::builder("/tenant", ())
+                .add(Method::GET, "/", |_, req, res| {
+                    let url = req.url_for("tenant_home", &[]).unwrap_or_default();
+                    res.status(StatusCode::OK).body(format!("tenant {} at {}", req.captures().get("tenant").unwrap(), url));
+                })
+                .with_name("tenant_home")
+                .build()
+                .expect("Invalid controller routes");
+
             router.add(basic_test_cont)
                 .route("/test", basic_test_cont2)
+                .host("<tenant>.localhost", |router| router.add(tenant_cont))
         })
         .configure_listener(|listener_config| {
             listener_config.set_uri("http://0.0.0.0:12345")
@@ -0,0 +1,75 @@
+extern crate saphir;
+
+use saphir::*;
+
+struct TestControllerContext {
+    pub resource: String,
+}
+
+#[controller(name = "test")]
+impl TestControllerContext {
+    #[get("/")]
+    fn read(&self, _req: &SyncRequest, res: &mut SyncResponse) {
+        res.status(StatusCode::OK).body(format!("this is working nicely!\r\n the context string is : {}", self.resource));
+    }
+
+    #[post("/")]
+    fn create(&self, req: &SyncRequest, res: &mut SyncResponse) {
+        if let Ok(location) = req.url_for("claim", &[("claim", "firstname")]) {
+            res.status(StatusCode::CREATED).header(header::LOCATION, location.as_str());
+        }
+    }
+
+    #[put("/with-body")]
+    #[guard(BodyGuard)]
+    fn update(&self, _req: &SyncRequest, _res: &mut SyncResponse) {
+        println!("this is only reachable if the request has a body")
+    }
+
+    #[get("/report", produces = "application/json")]
+    fn json_report(&self, _req: &SyncRequest, res: &mut SyncResponse) {
+        res.status(StatusCode::OK).header(header::CONTENT_TYPE, "application/json").body(format!("{{\"resource\":\"{}\"}}", self.resource));
+    }
+
+    #[get("/report", produces = "text/csv")]
+    fn csv_report(&self, _req: &SyncRequest, res: &mut SyncResponse) {
+        res.status(StatusCode::OK).header(header::CONTENT_TYPE, "text/csv").body(format!("resource\r\n{}\r\n", self.resource));
+    }
+
+    #[post("/report", consumes = "text/csv")]
+    fn import_report(&self, req: &SyncRequest, res: &mut SyncResponse) {
+        res.status(StatusCode::OK).body(format!("imported {} bytes", req.body().len()));
+    }
+
+    #[get("/<claim#r(^(firstname)|(lastname)$)>", name = "claim")]
+    fn claim(&self, req: &SyncRequest, res: &mut SyncResponse) {
+        res.status(StatusCode::OK).body(format!("requested claim: {:?}", req.captures().get("claim")));
+    }
+}
+
+fn main() {
+    let server_builder = Server::builder();
+
+    let server = server_builder
+        .configure_router(|router| {
+            let context = TestControllerContext { resource: "this is a private resource".to_string() };
+
+            // This will add the controller and so the following method+route will be valid
+            // GET  /test/
+            // POST /test/
+            // PUT  /test/with-body
+            // GET  /test/report, as json or csv depending on the Accept header
+            // POST /test/report, only with a csv body
+            // GET  /test/<claim>
+            router.add(context.into_controller().expect("Invalid controller routes"))
+        })
+        .configure_listener(|listener_config| {
+            listener_config.set_uri("http://0.0.0.0:12345")
+        })
+        .build();
+
+    if let Err(e) = server.run() {
+        println!("{:?}", e);
+        assert!(false);
+    }
+}
@@ -11,6 +11,19 @@ impl Middleware for LoggerMiddleware {
     }
 }
 
+struct AdminMiddleware {}
+
+impl Middleware for AdminMiddleware {
+    fn resolve(&self, req: &mut SyncRequest, res: &mut SyncResponse) -> RequestContinuation {
+        if req.headers_map().contains_key("X-Admin-Token") {
+            RequestContinuation::Continue
+        } else {
+            res.status(StatusCode::FORBIDDEN);
+            RequestContinuation::Stop
+        }
+    }
+}
+
 struct TestControllerContext {
     pub resource: String,
 }
@@ -32,14 +45,65 @@ fn main() {
 
     let server = server_builder
         .configure_middlewares(|stack| {
+            let cors = Cors::builder()
+                .allow_origin("http://localhost:8080")
+                .allow_origin_regex(r"^https://[a-z0-9-]+\.example\.com$")
+                .allow_header("Content-Type")
+                .expose_header("X-Request-Id")
+                .allow_credentials()
+                .max_age(600)
+                .build();
+
+            let sessions = Sessions::builder(MemoryStore::new())
+                .idle_timeout(std::time::Duration::from_secs(5 * 60))
+                .absolute_timeout(std::time::Duration::from_secs(60 * 60))
+                .build();
+
             stack.apply(LoggerMiddleware {}, vec!("/"), None)
+                .apply(cors, vec!("/test"), None)
+                .apply(sessions, vec!("/account"), None)
         })
         .configure_router(|router| {
-            let basic_test_cont = BasicController::new("/test", TestControllerContext::new("this is a private resource"));
+            let basic_test_cont = BasicController::builder("/test", TestControllerContext::new("this is a private resource"))
+                .add(Method::GET, "/", TestControllerContext::function_to_receive_a_get_http_call)
+                .add(Method::PUT, "/", TestControllerContext::function_to_receive_a_get_http_call)
+                .build()
+                .expect("Invalid controller routes");
+
+            let admin_cont = BasicController::builder("/users", ())
+                .add(Method::GET, "/", |_, _, res| { res.status(StatusCode::OK).body("admin users"); })
+                .build()
+                .expect("Invalid controller routes");
+
+            let account_cont = BasicController::builder("/account", ())
+                .add(Method::GET, "/", |_, req, res| {
+                    match req.require_state::<Session>().ok().and_then(|s| s.get::<String>("user")) {
+                        Some(user) => res.status(StatusCode::OK).body(format!("logged in as {}", user)),
+                        None => res.status(StatusCode::UNAUTHORIZED),
+                    };
+                })
+                .add(Method::POST, "/login", |_, req, res| {
+                    if let Ok(session) = req.require_state::<Session>() {
+                        session.regenerate();
+                        session.insert("user", String::from_utf8_lossy(req.body()));
+                        res.status(StatusCode::NO_CONTENT);
+                    }
+                })
+                .add(Method::POST, "/logout", |_, req, res| {
+                    if let Ok(session) = req.require_state::<Session>() {
+                        session.destroy();
+                        res.status(StatusCode::NO_CONTENT);
+                    }
+                })
+                .build()
+                .expect("Invalid controller routes");
 
-            basic_test_cont.add(Method::GET, "/", TestControllerContext::function_to_receive_a_get_http_call);
+            let admin_router = router::Builder::new().add(admin_cont).build().expect("Invalid routes");
+            let admin_middlewares = middleware::Builder::new().apply(AdminMiddleware {}, vec!("/"), None).build();
 
             router.add(basic_test_cont)
+                .add(account_cont)
+                .mount("/admin", admin_router, admin_middlewares)
         })
         .configure_listener(|listener_config| {
             listener_config.set_uri("http://0.0.0.0:12345")
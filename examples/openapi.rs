@@ -0,0 +1,40 @@
+extern crate saphir;
+
+use saphir::*;
+use saphir::openapi::OpenApiInfo;
+
+fn main() {
+    let server = Server::builder()
+        .configure_router(|router| {
+            let users = BasicController::builder("/users", ())
+                .add(Method::GET, "/<id#r(^[0-9]+$)>", |_, req, res| {
+                    res.status(StatusCode::OK).body(format!("{{\"id\": {}}}", req.captures()["id"]));
+                })
+                .with_doc(RouteDoc::new()
+                    .summary("Read a user")
+                    .tag("users")
+                    .response_with_body(200, "The user", "application/json", r#"{"type": "object", "properties": {"id": {"type": "integer"}}}"#)
+                    .response(404, "No user with this id"))
+                .add_with_guards(Method::POST, "/", BodyGuard.into(), |_, _, res| { res.status(StatusCode::CREATED); })
+                .with_doc(RouteDoc::new()
+                    .summary("Create a user")
+                    .tag("users")
+                    .request_body("application/json", r#"{"type": "object"}"#)
+                    .response(201, "The user was created"))
+                .build()
+                .expect("Invalid controller routes");
+
+            // GET /openapi.json returns the OpenAPI document of the users controller
+            router.add(users)
+                .add_openapi("/openapi.json", OpenApiInfo::new("Users", "1.0.0").server("http://localhost:12345"))
+        })
+        .configure_listener(|listener_config| {
+            listener_config.set_uri("http://0.0.0.0:12345")
+        })
+        .build();
+
+    if let Err(e) = server.run() {
+        println!("{:?}", e);
+        assert!(false);
+    }
+}
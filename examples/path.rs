@@ -52,6 +52,10 @@ impl UserControllerContext {
                 self.users.write().insert(user_id, user);
 
                 res.status(200).body(serde_json::to_vec(&json!({"UserId": user_id})).expect("This is valid json")).header("Content-Type", "application/json");
+
+                if let Ok(location) = req.url_for("read_user", &[("user-id", &user_id.to_string())]) {
+                    res.header(header::LOCATION, location.as_str());
+                }
             }
             Err(_e) => {
                 res.status(400);
@@ -62,7 +66,7 @@ impl UserControllerContext {
     pub fn read(&self, req: &SyncRequest, res: &mut SyncResponse) {
         let users = self.users.read();
         if let Some(user) = req.captures().get("user-id").and_then(|user_id_str| user_id_str.parse::<usize>().ok()).and_then(|u_id| users.get(&u_id)) {
-            let json = match req.captures().get("claim").as_ref().map(|s| s.as_str()) {
+            let json = match req.captures().get("claim").map(|s| s.to_lowercase()).as_ref().map(|s| s.as_str()) {
                 Some("firstname") => {
                     json! ({
                         "Firstname": &user.first_name
@@ -84,6 +88,18 @@ impl UserControllerContext {
         }
     }
 
+    pub fn list(&self, req: &SyncRequest, res: &mut SyncResponse) {
+        // The page segment is optional, /user/list is the first page
+        let page = req.captures().get("page").and_then(|p| p.parse::<usize>().ok()).unwrap_or(0);
+
+        let users = self.users.read();
+        let mut ids: Vec<&usize> = users.keys().collect();
+        ids.sort();
+        let page_ids: Vec<&usize> = ids.into_iter().skip(page * 10).take(10).collect();
+
+        res.status(200).body(serde_json::to_vec(&json!({"UserIds": page_ids})).expect("This is valid json")).header("Content-Type", "application/json");
+    }
+
     pub fn update(&self, req: &SyncRequest, res: &mut SyncResponse) {
         let mut users = self.users.write();
         if let Some(user) = req.captures().get("user-id").and_then(|user_id_str| user_id_str.parse::<usize>().ok()).and_then(|u_id| users.get_mut(&u_id)) {
@@ -128,23 +144,40 @@ fn main() {
             stack.apply(LoggerMiddleware {}, vec!("/user/<_#r(^[0-9]*$)>"), None)
         })
         .configure_router(|router| {
-            let basic_test_cont = BasicController::new("/user", UserControllerContext::new());
-
-            basic_test_cont.add_with_guards(Method::POST, "/", BodyGuard.into(), UserControllerContext::create);
-
-            basic_test_cont.add(Method::GET, "/<user-id>", UserControllerContext::read);
-
-            basic_test_cont.add(Method::GET, "/<user-id>/<claim#r(^(firstname)|(lastname)$)>", UserControllerContext::read);
-
-            basic_test_cont.add(Method::PUT, "/<user-id>", UserControllerContext::update);
-
-            basic_test_cont.add(Method::DELETE, "/<user-id#r(^[0-9]*$)>", UserControllerContext::delete);
-
-            router.add(basic_test_cont)
+            let basic_test_cont = BasicController::builder("/user", UserControllerContext::new())
+                .add_with_guards(Method::POST, "", BodyGuard.into(), UserControllerContext::create)
+                .add(Method::GET, "/list/<page#r(^[0-9]+$)>?", UserControllerContext::list)
+                .add(Method::GET, "/<user-id>", UserControllerContext::read)
+                .with_name("read_user")
+                .with_doc(RouteDoc::new().summary("Read a user").tag("users")
+                    .response_with_body(200, "The user", "application/json", r#"{"type": "object", "properties": {"first_name": {"type": "string"}, "last_name": {"type": "string"}}}"#)
+                    .response(404, "No user with this id"))
+                .add(Method::GET, "/<user-id>/<claim#r(^(firstname)|(lastname)$)i>", UserControllerContext::read)
+                .add(Method::PUT, "/<user-id>", UserControllerContext::update)
+                .add(Method::DELETE, "/<user-id#r(^[0-9]*$)>", UserControllerContext::delete)
+                .build()
+                .expect("Invalid controller routes");
+
+            // A controller mounted on a tail segment captures the rest of the path, it is only tried after every other controller
+            let fallback_cont = BasicController::builder("/<path..>", ())
+                .add(Method::GET, "/", |_, req, res| {
+                    let path = req.captures().get("path").cloned().unwrap_or_default();
+                    res.status(404).body(format!("Nothing found at /{}", path));
+                })
+                .build()
+                .expect("Invalid controller routes");
+
+            // GET /_routes lists every route of the router, static segments are matched regardless of case (e.g. /USER/list)
+            router.add(fallback_cont)
+                .add(basic_test_cont)
+                .add_route_listing("/_routes")
+                .case_insensitive()
         })
         .configure_listener(|listener_config| {
             listener_config.set_uri("http://0.0.0.0:12345")
         })
+        // GET /user/list/ is redirected to /user/list
+        .trailing_slash(TrailingSlash::Redirect)
         .build();
 
     if let Err(e) = server.run() {
@@ -0,0 +1 @@
+console.log("served by saphir");
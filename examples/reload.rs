@@ -0,0 +1,45 @@
+extern crate saphir;
+
+use saphir::*;
+use std::thread;
+use std::time::Duration;
+
+fn version_controller(version: &'static str) -> BasicController<&'static str> {
+    BasicController::builder("/version", version)
+        .add(Method::GET, "/", |version, _, res| { res.status(StatusCode::OK).body(version.to_string()); })
+        .build()
+        .expect("Invalid controller routes")
+}
+
+fn main() {
+    let server = Server::builder()
+        .configure_router(|router| {
+            router.add(version_controller("v1"))
+        })
+        .configure_listener(|listener_config| {
+            listener_config.set_uri("http://0.0.0.0:12345")
+        })
+        .build();
+
+    let reload_handle = server.reload_handle();
+
+    // After 10 seconds, GET /version/ answers with v2 and GET /feature/ becomes available, without restarting the server
+    thread::spawn(move || {
+        thread::sleep(Duration::from_secs(10));
+
+        reload_handle.reconfigure_router(|router| {
+            let feature = BasicController::builder("/feature", ())
+                .add(Method::GET, "/", |_, _, res| { res.status(StatusCode::OK).body("this feature was just enabled"); })
+                .build()
+                .expect("Invalid controller routes");
+
+            router.add(version_controller("v2"))
+                .add(feature)
+        }).expect("Invalid routes");
+    });
+
+    if let Err(e) = server.run() {
+        println!("{:?}", e);
+        assert!(false);
+    }
+}
@@ -0,0 +1,44 @@
+extern crate saphir;
+
+use saphir::*;
+
+fn main() {
+    let server_builder = Server::builder();
+
+    let server = server_builder
+        .configure_router(|router| {
+            let api_cont = BasicController::builder("/api", ())
+                .add(Method::GET, "/status", |_, _, res| { res.status(StatusCode::OK).body("ok"); })
+                .build()
+                .expect("Invalid controller routes");
+
+            // Every path which is not an api route serves a file of the public directory, paths without any matching
+            // file and without extension serve the index, letting the page route on the client side
+            let public_cont = StaticFilesController::builder("/", concat!(env!("CARGO_MANIFEST_DIR"), "/examples/public"))
+                .spa_fallback("index.html")
+                .build()
+                .expect("Unable to serve the public directory");
+
+            // The same assets compiled in the binary, with a precompressed variant served to clients accepting gzip
+            let embedded_cont = EmbeddedAssetsController::builder("/embedded")
+                .asset("index.html", include_bytes!("public/index.html"))
+                .asset("app.js", include_bytes!("public/app.js"))
+                .gzip(include_bytes!("public/app.js.gz"))
+                .asset("docs/index.html", include_bytes!("public/docs/index.html"))
+                .build()
+                .expect("Invalid embedded assets");
+
+            router.add(api_cont)
+                .add(embedded_cont)
+                .add(public_cont)
+        })
+        .configure_listener(|listener_config| {
+            listener_config.set_uri("http://0.0.0.0:12345")
+        })
+        .build();
+
+    if let Err(e) = server.run() {
+        println!("{:?}", e);
+        assert!(false);
+    }
+}
@@ -0,0 +1,324 @@
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+//! # Saphir Macro
+//!
+//! Procedural macros for the saphir http server framework. Those macros are re-exported by saphir when the `macro` feature is
+//! enabled, there is no need to depend on this crate directly.
+//!
+//! The `controller` attribute is applied on an `impl` block. Every method of the block annotated with an http method attribute
+//! (`get`, `post`, `put`, `patch`, `delete`, `head`, `options`) is registered as a delegate of a `BasicController`, using the
+//! `controller` name as base path. Methods can also be annotated with one or many `guard` attributes, each one taking a list of
+//! expressions evaluating to a `RequestGuard`. A route can be named for url generation with `#[get("/<id>", name = "read_user")]`,
+//! and restricted to media types with `consumes` and `produces`, e.g. `#[get("/", produces = "text/csv")]`.
+//!
+//! ```rust,ignore
+//! struct UsersContext;
+//!
+//! #[controller(name = "users")]
+//! impl UsersContext {
+//!     #[get("/<id>", name = "read_user")]
+//!     fn read(&self, req: &SyncRequest, res: &mut SyncResponse) {
+//!         res.status(StatusCode::OK);
+//!     }
+//!
+//!     #[put("/<id>")]
+//!     #[guard(BodyGuard)]
+//!     fn update(&self, req: &SyncRequest, res: &mut SyncResponse) {
+//!         res.status(StatusCode::OK);
+//!     }
+//! }
+//!
+//! // GET /users/<id> and PUT /users/<id> are now routed to the context
+//! router.add(UsersContext.into_controller().expect("Invalid controller routes"))
+//! ```
+
+extern crate proc_macro;
+
+use proc_macro::TokenStream;
+use proc_macro2::{Span, TokenStream as TokenStream2};
+use quote::quote;
+use syn::parse::{Parse, ParseStream};
+use syn::punctuated::Punctuated;
+use syn::{parse_macro_input, AttributeArgs, Error, Expr, ImplItem, ItemImpl, Lit, LitStr, Meta, NestedMeta, Token};
+
+const METHOD_ATTRIBUTES: &[(&str, &str)] = &[
+    ("get", "GET"),
+    ("post", "POST"),
+    ("put", "PUT"),
+    ("patch", "PATCH"),
+    ("delete", "DELETE"),
+    ("head", "HEAD"),
+    ("options", "OPTIONS"),
+];
+
+const GUARD_ATTRIBUTE: &str = "guard";
+
+/// Generates a `BasicController` out of an `impl` block, see the crate documentation for usage.
+///
+/// The annotated type gains an `into_controller` method and the `BasicController` type can also be obtained with `TryFrom`.
+/// Paths, regexes and route names are validated at compile time, media types and delegates sharing a path when the
+/// controller is built.
+#[proc_macro_attribute]
+pub fn controller(args: TokenStream, input: TokenStream) -> TokenStream {
+    let args = parse_macro_input!(args as AttributeArgs);
+    let item = parse_macro_input!(input as ItemImpl);
+
+    match expand_controller(args, item) {
+        Ok(tokens) => tokens.into(),
+        Err(e) => e.to_compile_error().into(),
+    }
+}
+
+struct Route {
+    method: syn::Ident,
+    args: RouteArgs,
+}
+
+/// Arguments of an http method attribute: a path, optionally followed by `name = "..."`, `consumes = "..."` and
+/// `produces = "..."`
+struct RouteArgs {
+    path: LitStr,
+    name: Option<LitStr>,
+    consumes: Vec<LitStr>,
+    produces: Vec<LitStr>,
+}
+
+impl Parse for RouteArgs {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        let path = input.parse()?;
+        let mut args = RouteArgs { path, name: None, consumes: Vec::new(), produces: Vec::new() };
+
+        while input.parse::<Option<Token![,]>>()?.is_some() && !input.is_empty() {
+            let key: syn::Ident = input.parse()?;
+            input.parse::<Token![=]>()?;
+            let value: LitStr = input.parse()?;
+
+            if key == "name" {
+                args.name = Some(value);
+            } else if key == "consumes" || key == "produces" {
+                if !value.value().contains('/') {
+                    return Err(Error::new_spanned(value, "Invalid media type, expected `type/subtype`"));
+                }
+
+                if key == "consumes" {
+                    args.consumes.push(value);
+                } else {
+                    args.produces.push(value);
+                }
+            } else {
+                return Err(Error::new_spanned(key, "Unknown route argument, expected `name`, `consumes` or `produces`"));
+            }
+        }
+
+        Ok(args)
+    }
+}
+
+struct GuardList {
+    guards: Punctuated<Expr, Token![,]>,
+}
+
+impl Parse for GuardList {
+    fn parse(input: ParseStream) -> syn::Result<Self> {
+        Ok(GuardList {
+            guards: Punctuated::parse_terminated(input)?,
+        })
+    }
+}
+
+fn expand_controller(args: AttributeArgs, mut item: ItemImpl) -> Result<TokenStream2, Error> {
+    let base_path = controller_base_path(&args)?;
+
+    let mut registrations = Vec::new();
+    let mut registered: Vec<(String, String)> = Vec::new();
+    let mut names: Vec<String> = Vec::new();
+
+    for impl_item in item.items.iter_mut() {
+        let method = match impl_item {
+            ImplItem::Method(ref mut m) => m,
+            _ => continue,
+        };
+
+        let mut routes = Vec::new();
+        let mut guards = Vec::new();
+        let mut kept_attrs = Vec::with_capacity(method.attrs.len());
+
+        for attr in method.attrs.drain(..) {
+            if let Some(&(_, http_method)) = METHOD_ATTRIBUTES.iter().find(|(name, _)| attr.path.is_ident(name)) {
+                let args: RouteArgs = attr.parse_args()?;
+                validate_path(&args.path)?;
+                routes.push(Route {
+                    method: syn::Ident::new(http_method, Span::call_site()),
+                    args,
+                });
+            } else if attr.path.is_ident(GUARD_ATTRIBUTE) {
+                let list: GuardList = attr.parse_args()?;
+                guards.extend(list.guards);
+            } else {
+                kept_attrs.push(attr);
+            }
+        }
+
+        method.attrs = kept_attrs;
+
+        if routes.is_empty() {
+            if let Some(guard) = guards.first() {
+                return Err(Error::new_spanned(guard, "A guard can only be applied on a method annotated with an http method"));
+            }
+            continue;
+        }
+
+        let fn_name = &method.sig.ident;
+
+        for Route { method, args: RouteArgs { path, name, consumes, produces } } in routes {
+            // Delegates sharing a path can be told apart by their media types, those are checked when building the controller
+            if consumes.is_empty() && produces.is_empty() {
+                let key = (method.to_string(), normalize_path(&path.value()));
+                if registered.contains(&key) {
+                    return Err(Error::new_spanned(&path, format!("A delegate is already registered for {} {}", key.0, path.value())));
+                }
+                registered.push(key);
+            }
+
+            if let Some(ref name) = name {
+                if names.contains(&name.value()) {
+                    return Err(Error::new_spanned(name, format!("A delegate is already named {}", name.value())));
+                }
+                names.push(name.value());
+            }
+
+            let registration = if guards.is_empty() {
+                quote! {
+                    .add(::saphir::Method::#method, #path, Self::#fn_name)
+                }
+            } else {
+                quote! {
+                    .add_with_guards(::saphir::Method::#method, #path, {
+                        let mut guards = ::saphir::RequestGuardCollection::new();
+                        #(guards.add(#guards);)*
+                        guards
+                    }, Self::#fn_name)
+                }
+            };
+
+            registrations.push(registration);
+
+            if let Some(name) = name {
+                registrations.push(quote! {
+                    .with_name(#name)
+                });
+            }
+
+            registrations.extend(consumes.iter().map(|media_type| quote! { .consumes(#media_type) }));
+            registrations.extend(produces.iter().map(|media_type| quote! { .produces(#media_type) }));
+        }
+    }
+
+    let self_ty = &item.self_ty;
+    let (impl_generics, _, where_clause) = item.generics.split_for_impl();
+
+    Ok(quote! {
+        #item
+
+        impl #impl_generics #self_ty #where_clause {
+            /// Consumes the context and creates a `BasicController` with every annotated method registered as a delegate.
+            /// Fails like `controller::Builder::build`, e.g. on two delegates that cannot be told apart by their media types.
+            pub fn into_controller(self) -> ::std::result::Result<::saphir::BasicController<Self>, ::saphir::RouteError> {
+                ::saphir::BasicController::builder(#base_path, self)
+                    #(#registrations)*
+                    .build()
+            }
+        }
+
+        impl #impl_generics ::std::convert::TryFrom<#self_ty> for ::saphir::BasicController<#self_ty> #where_clause {
+            type Error = ::saphir::RouteError;
+
+            fn try_from(context: #self_ty) -> ::std::result::Result<Self, Self::Error> {
+                context.into_controller()
+            }
+        }
+    })
+}
+
+fn controller_base_path(args: &[NestedMeta]) -> Result<String, Error> {
+    let mut name = None;
+
+    for arg in args {
+        match arg {
+            NestedMeta::Meta(Meta::NameValue(ref nv)) if nv.path.is_ident("name") => {
+                if let Lit::Str(ref s) = nv.lit {
+                    name = Some(s.value());
+                } else {
+                    return Err(Error::new_spanned(&nv.lit, "The controller name must be a string literal"));
+                }
+            }
+            _ => return Err(Error::new_spanned(arg, "Unknown controller argument, expected `name = \"...\"`")),
+        }
+    }
+
+    let name = name.ok_or_else(|| Error::new(Span::call_site(), "Missing controller name, expected `#[controller(name = \"...\")]`"))?;
+
+    if name.starts_with('/') {
+        Ok(name)
+    } else {
+        Ok(format!("/{}", name))
+    }
+}
+
+/// Normalizes a route so that equivalent paths (e.g. `/a` and `a`) are detected as duplicates, the trailing slash being kept
+fn normalize_path(path: &str) -> String {
+    let normalized = path.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>().join("/");
+
+    if path.len() > 1 && path.ends_with('/') {
+        format!("{}/", normalized)
+    } else {
+        normalized
+    }
+}
+
+/// Validates a route at compile time using the same rules as the runtime path matcher
+fn validate_path(path: &LitStr) -> Result<(), Error> {
+    let value = path.value();
+    let segments: Vec<&str> = value.split('/').filter(|s| !s.is_empty()).collect();
+
+    let mut optional_found = false;
+
+    for (index, segment) in segments.iter().enumerate() {
+        let optional = segment.starts_with('<') && segment.ends_with(">?");
+        if optional_found && !optional {
+            return Err(Error::new_spanned(path, "Optional segments should be the last segments of the path"));
+        }
+        optional_found |= optional;
+
+        if !segment.starts_with('<') {
+            continue;
+        }
+
+        let segment = segment.trim_end_matches('?');
+
+        if optional && segment.ends_with("..>") && !segment.contains("#r") {
+            return Err(Error::new_spanned(path, format!("The tail segment `{}` cannot be optional", segment)));
+        }
+
+        if segment.ends_with("..>") && !segment.contains("#r") && index + 1 < segments.len() {
+            return Err(Error::new_spanned(path, format!("The tail segment `{}` should be the last segment of the path", segment)));
+        }
+
+        if !segment.ends_with('>') {
+            return Err(Error::new_spanned(path, format!("The variable path segment `{}` should start with < & end with >", segment)));
+        }
+
+        let inner = segment.trim_start_matches('<').trim_end_matches('>');
+        if let Some(pos) = inner.find("#r") {
+            let pattern = saphir_regex_flags::regex_with_flags(&inner[pos + 2..])
+                .map_err(|e| Error::new_spanned(path, format!("{} in path segment `{}`", e, segment)))?;
+
+            if let Err(e) = regex::Regex::new(&pattern) {
+                return Err(Error::new_spanned(path, format!("Invalid regex in path segment `{}`: {}", segment, e)));
+            }
+        }
+    }
+
+    Ok(())
+}
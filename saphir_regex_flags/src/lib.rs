@@ -0,0 +1,27 @@
+#![deny(missing_docs)]
+#![deny(warnings)]
+
+//! # Saphir Regex Flags
+//!
+//! Parsing of the regex flags of the custom path segments, e.g. `<name#r(^[a-z]+$)i>`. It is shared by saphir and
+//! saphir_macro, so the paths validated at compile time follow the same rules as the runtime path matcher. There is no
+//! need to depend on this crate directly.
+
+/// Parse the regex of a custom segment, written as `(regex)` optionally followed by flags, e.g. `(^[a-z]+$)i`.
+/// The flags are the inline flags supported by the regex crate: `i`, `m`, `s`, `U` and `x`.
+pub fn regex_with_flags(r: &str) -> Result<String, String> {
+    let (pattern, flags) = match r.rfind(')') {
+        Some(end) if r.starts_with('(') => (&r[1..end], &r[end + 1..]),
+        _ => (r.trim_start_matches('(').trim_end_matches(')'), ""),
+    };
+
+    if let Some(flag) = flags.chars().find(|c| !"imsUx".contains(*c)) {
+        return Err(format!("Unknown regex flag `{}`, supported flags are i, m, s, U and x", flag));
+    }
+
+    if flags.is_empty() {
+        Ok(pattern.to_string())
+    } else {
+        Ok(format!("(?{}){}", flags, pattern))
+    }
+}
@@ -0,0 +1,266 @@
+use std::io::{Read, Write};
+
+use flate2::Compression as FlateLevel;
+use flate2::read::{MultiGzDecoder, ZlibDecoder, DeflateDecoder};
+use flate2::write::{GzEncoder, ZlibEncoder};
+use futures::{stream, Async, Stream};
+use futures::executor::{self, Notify, NotifyHandle};
+
+use crate::error::BodyError;
+use crate::http::*;
+use crate::http::header::Header;
+use crate::utils::preferred_encoding;
+
+/// A content coding the responses can be compressed with
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContentCoding {
+    /// The `br` coding
+    Brotli,
+    /// The `gzip` coding
+    Gzip,
+    /// The `deflate` coding
+    Deflate,
+}
+
+impl ContentCoding {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            ContentCoding::Brotli => "br",
+            ContentCoding::Gzip => "gzip",
+            ContentCoding::Deflate => "deflate",
+        }
+    }
+
+    fn encoding(&self) -> header::Encoding {
+        match *self {
+            ContentCoding::Brotli => header::Encoding::Brotli,
+            ContentCoding::Gzip => header::Encoding::Gzip,
+            ContentCoding::Deflate => header::Encoding::Deflate,
+        }
+    }
+
+    fn encode(&self, body: &[u8], level: u32) -> ::std::io::Result<Vec<u8>> {
+        match *self {
+            ContentCoding::Brotli => {
+                let mut encoder = brotli::CompressorWriter::new(Vec::new(), 4096, level, 22);
+                encoder.write_all(body)?;
+                encoder.flush()?;
+                Ok(encoder.into_inner())
+            }
+            ContentCoding::Gzip => {
+                let mut encoder = GzEncoder::new(Vec::new(), FlateLevel::new(level));
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+            ContentCoding::Deflate => {
+                let mut encoder = ZlibEncoder::new(Vec::new(), FlateLevel::new(level));
+                encoder.write_all(body)?;
+                encoder.finish()
+            }
+        }
+    }
+}
+
+/// Configuration of the response compression, see `server::Builder::compression`
+/// # Example
+/// ```rust,no_run
+/// let server = Server::builder()
+///     .compression(Compression::new().min_size(512).content_types(&["application/json"]))
+///     .build();
+/// ```
+#[derive(Debug, Clone)]
+pub struct Compression {
+    codings: Vec<ContentCoding>,
+    content_types: Vec<String>,
+    min_size: u64,
+    level: u32,
+}
+
+impl Compression {
+    /// Compress with brotli, gzip or deflate, in this order of preference, the textual responses of at least 1 KiB
+    pub fn new() -> Self {
+        Compression {
+            codings: vec![ContentCoding::Brotli, ContentCoding::Gzip, ContentCoding::Deflate],
+            content_types: vec![
+                "text/*".to_string(),
+                "application/json".to_string(),
+                "application/javascript".to_string(),
+                "application/xml".to_string(),
+                "image/svg+xml".to_string(),
+            ],
+            min_size: 1024,
+            level: 6,
+        }
+    }
+
+    /// Set the codings the responses can be compressed with, in order of preference when a client accepts several
+    /// of them with the same quality
+    pub fn codings(mut self, codings: &[ContentCoding]) -> Self {
+        self.codings = codings.to_vec();
+        self
+    }
+
+    /// Set the media types of the compressed responses, either exact, e.g. `application/json`, or any subtype of a
+    /// type, e.g. `text/*`. Media types with a `+json` or `+xml` suffix are compressed as well.
+    pub fn content_types(mut self, content_types: &[&str]) -> Self {
+        self.content_types = content_types.iter().map(|c| c.to_lowercase()).collect();
+        self
+    }
+
+    /// Set the size in bytes under which responses are not compressed
+    pub fn min_size(mut self, min_size: u64) -> Self {
+        self.min_size = min_size;
+        self
+    }
+
+    /// Set the compression level, from 0 (fastest) to 9 (smallest)
+    pub fn level(mut self, level: u32) -> Self {
+        self.level = level.min(9);
+        self
+    }
+
+    fn compressible(&self, content_type: &str) -> bool {
+        let media_type = content_type.split(';').next().unwrap_or("").trim().to_lowercase();
+        let main_type = media_type.split('/').next().unwrap_or("");
+
+        media_type.ends_with("+json") || media_type.ends_with("+xml") || self.content_types.iter().any(|c| {
+            *c == media_type || (c.ends_with("/*") && c[..c.len() - 2] == *main_type)
+        })
+    }
+
+    /// The preferred coding accepted by a client, none if it accepts none of the configured codings
+    fn negotiate(&self, accept_encoding: &header::AcceptEncoding) -> Option<ContentCoding> {
+        let codings: Vec<(ContentCoding, header::Encoding)> = self.codings.iter().map(|c| (*c, c.encoding())).collect();
+        preferred_encoding(accept_encoding, &codings)
+    }
+
+    /// Compress the body of a response with the preferred coding accepted by the client. Responses already encoded,
+    /// partial, without content, with a streaming body, of a media type not configured or smaller than the minimum
+    /// size are left untouched. The response is compressed on the calling thread, as encoding would stall the event loop.
+    pub(crate) fn compress(&self, req: &SyncRequest, res: Response<Body>) -> Response<Body> {
+        let status = res.status();
+        if req.method() == Method::HEAD || status == StatusCode::NO_CONTENT || status == StatusCode::NOT_MODIFIED
+            || status == StatusCode::PARTIAL_CONTENT || status.is_informational() {
+            return res;
+        }
+
+        {
+            let headers = res.headers();
+            let content_type = headers.get(header::CONTENT_TYPE).and_then(|c| c.to_str().ok()).unwrap_or("");
+            let no_transform = headers.get_all(header::CACHE_CONTROL).iter()
+                .any(|c| c.to_str().map(|c| c.to_lowercase().contains("no-transform")).unwrap_or(false));
+            let length = res.body().content_length();
+
+            if headers.contains_key(header::CONTENT_ENCODING) || headers.contains_key(header::CONTENT_RANGE) || no_transform
+                || !self.compressible(content_type) || length.map_or(true, |l| l < self.min_size) {
+                return res;
+            }
+        }
+
+        let accept_encoding = header::AcceptEncoding::parse_header(&req.headers_map().get_all(header::ACCEPT_ENCODING))
+            .unwrap_or_else(|_| header::AcceptEncoding(Vec::new()));
+
+        let (mut parts, body) = res.into_parts();
+        // The vary header is added whatever the coding, as the representation depends on the accepted codings
+        parts.headers.append(header::VARY, header::HeaderValue::from_static("Accept-Encoding"));
+
+        let coding = match self.negotiate(&accept_encoding) {
+            Some(coding) => coding,
+            None => return Response::from_parts(parts, body),
+        };
+
+        let body = match in_memory_content(body) {
+            Ok(body) => body,
+            Err(body) => return Response::from_parts(parts, body),
+        };
+
+        match coding.encode(&body, self.level) {
+            Ok(compressed) => {
+                parts.headers.insert(header::CONTENT_ENCODING, header::HeaderValue::from_static(coding.as_str()));
+                parts.headers.remove(header::CONTENT_LENGTH);
+
+                // The compressed representation is no longer byte for byte identical to the original one
+                let weak_etag = parts.headers.get(header::ETAG)
+                    .and_then(|e| e.to_str().ok())
+                    .filter(|e| !e.starts_with("W/"))
+                    .and_then(|e| header::HeaderValue::from_str(&format!("W/{}", e)).ok());
+                if let Some(etag) = weak_etag {
+                    parts.headers.insert(header::ETAG, etag);
+                }
+
+                Response::from_parts(parts, Body::from(compressed))
+            }
+            Err(_) => Response::from_parts(parts, Body::from(body)),
+        }
+    }
+}
+
+struct NoopNotify;
+
+impl Notify for NoopNotify {
+    fn notify(&self, _id: usize) {}
+}
+
+static NOOP_NOTIFY: NoopNotify = NoopNotify;
+
+/// Read the content of a body held in memory without waiting on it. A body which is not ready at once, i.e. a streaming
+/// one, is given back with the chunks already read.
+fn in_memory_content(body: Body) -> Result<Vec<u8>, Body> {
+    let notify = NotifyHandle::from(&NOOP_NOTIFY);
+    let mut body = executor::spawn(body);
+    let mut content = Vec::new();
+
+    loop {
+        match body.poll_stream_notify(&notify, 0) {
+            Ok(Async::Ready(Some(chunk))) => content.extend_from_slice(&chunk),
+            Ok(Async::Ready(None)) => return Ok(content),
+            Ok(Async::NotReady) => {
+                let read = stream::once(Ok(::hyper::Chunk::from(content)));
+                return Err(Body::wrap_stream(read.chain(body.into_inner())));
+            }
+            Err(_) => return Err(Body::empty()),
+        }
+    }
+}
+
+/// Decode a request body with the codings of its `Content-Encoding` header, in the reverse order they were applied.
+/// The decoded body counts against the maximum body size, so a small compressed body cannot expand without limit.
+pub(crate) fn decode_request_body(headers: &mut header::HeaderMap, body: Vec<u8>, max_size: u64) -> Result<Vec<u8>, BodyError> {
+    let codings: Vec<String> = headers.get_all(header::CONTENT_ENCODING).iter()
+        .flat_map(|value| value.to_str().unwrap_or("").split(',').map(|c| c.trim().to_lowercase()).collect::<Vec<String>>())
+        .filter(|c| !c.is_empty() && c != "identity")
+        .collect();
+
+    if codings.is_empty() {
+        return Ok(body);
+    }
+
+    let mut body = body;
+    for coding in codings.iter().rev() {
+        body = match coding.as_str() {
+            "gzip" | "x-gzip" => read_limited(MultiGzDecoder::new(&body[..]), max_size, coding)?,
+            // Deflate bodies are expected in the zlib format, some clients send the raw format instead
+            "deflate" => read_limited(ZlibDecoder::new(&body[..]), max_size, coding)
+                .or_else(|e| match e {
+                    BodyError::InvalidEncoding(_) => read_limited(DeflateDecoder::new(&body[..]), max_size, coding),
+                    e => Err(e),
+                })?,
+            "br" => read_limited(brotli::Decompressor::new(&body[..], 4096), max_size, coding)?,
+            _ => return Err(BodyError::UnsupportedEncoding(coding.clone())),
+        };
+    }
+
+    headers.remove(header::CONTENT_ENCODING);
+    headers.insert(header::CONTENT_LENGTH, header::HeaderValue::from(body.len() as u64));
+
+    Ok(body)
+}
+
+fn read_limited<R: Read>(decoder: R, max_size: u64, coding: &str) -> Result<Vec<u8>, BodyError> {
+    let mut decoded = Vec::new();
+    match decoder.take(max_size + 1).read_to_end(&mut decoded) {
+        Ok(_) if decoded.len() as u64 > max_size => Err(BodyError::TooLarge),
+        Ok(_) => Ok(decoded),
+        Err(_) => Err(BodyError::InvalidEncoding(coding.to_string())),
+    }
+}
@@ -1,7 +1,13 @@
-use parking_lot::RwLock;
+use std::marker::PhantomData;
 
+use log::warn;
+use parking_lot::Mutex;
+
+use crate::error::RouteError;
 use crate::http::*;
-use crate::utils::UriPathMatcher;
+use crate::http::header::{Header, Quality, QualityItem};
+use crate::http::header::mime::{self, Mime};
+use crate::utils::{UriPathMatcher, UriPathSegmentMatcher};
 use crate::utils::RequestContinuation;
 
 /// Trait representing a controller
@@ -12,6 +18,139 @@ pub trait Controller: Send + Sync {
 
     /// Method used by the router to know were to route a request addressed at a controller
     fn base_path(&self) -> &str;
+
+    /// Method used by the router to list the routes handled by the controller, with paths relative to the base path.
+    /// Controllers not overriding this method are not listed by `Router::routes`.
+    fn routes(&self) -> Vec<RouteInfo> {
+        Vec::new()
+    }
+}
+
+/// Description of a registered route, as returned by `Router::routes`
+#[derive(Debug, Clone)]
+pub struct RouteInfo {
+    /// The http method of the route
+    pub method: Method,
+    /// The path pattern of the route, e.g. `/users/<id>`
+    pub path: String,
+    /// The segments of the path pattern
+    pub segments: Vec<RouteSegment>,
+    /// The names of the guards validated before reaching the route delegate
+    pub guards: Vec<String>,
+    /// The name of the controller handling the route
+    pub controller: String,
+    /// The name of the route, used to generate urls
+    pub name: Option<String>,
+    /// The documentation attached to the route, if any
+    pub doc: Option<RouteDoc>,
+    /// Whether the path pattern ends with a slash, e.g. `/users/`
+    pub trailing_slash: bool,
+    /// Whether the static segments of the path are matched regardless of case, see `router::Builder::case_insensitive`
+    pub case_insensitive: bool,
+    /// The media types of the request bodies accepted by the route, any when empty
+    pub consumes: Vec<String>,
+    /// The media types of the responses produced by the route, any when empty
+    pub produces: Vec<String>,
+    /// The api version the route belongs to, see `router::Builder::version`
+    pub version: Option<String>,
+    /// The host pattern the route is served on, see `router::Builder::host`
+    pub host: Option<String>,
+}
+
+/// A segment of a route path pattern
+#[derive(Debug, Clone, PartialEq)]
+pub enum RouteSegment {
+    /// A segment matched exactly
+    Static(String),
+    /// A segment matching any value, or only values matching `pattern` when provided.
+    /// The value is captured when the segment is named.
+    Variable {
+        /// The capture name
+        name: Option<String>,
+        /// The regex restricting the segment values
+        pattern: Option<String>,
+        /// Whether the segment can be omitted, e.g. `<id>?`
+        optional: bool,
+    },
+    /// The last segment of a path, matching the rest of the path. The value is captured when the segment is named.
+    Tail {
+        /// The capture name
+        name: Option<String>,
+    },
+}
+
+impl<'a> From<&'a UriPathSegmentMatcher> for RouteSegment {
+    fn from(segment: &'a UriPathSegmentMatcher) -> Self {
+        match segment {
+            UriPathSegmentMatcher::Static { segment: ref s } => RouteSegment::Static(s.clone()),
+            UriPathSegmentMatcher::Variable { name: ref n } => RouteSegment::Variable { name: n.clone(), pattern: None, optional: false },
+            UriPathSegmentMatcher::Custom { name: ref n, segment: ref s } => RouteSegment::Variable { name: n.clone(), pattern: Some(s.as_str().to_string()), optional: false },
+            UriPathSegmentMatcher::Tail { name: ref n } => RouteSegment::Tail { name: n.clone() },
+            UriPathSegmentMatcher::Optional { segment: ref s } => match RouteSegment::from(&**s) {
+                RouteSegment::Variable { name, pattern, .. } => RouteSegment::Variable { name, pattern, optional: true },
+                other => other,
+            },
+        }
+    }
+}
+
+/// Documentation attached to a route with `controller::Builder::with_doc`, used when generating an OpenAPI document.
+/// Schemas are JSON schema documents provided as strings.
+#[derive(Debug, Clone, Default)]
+pub struct RouteDoc {
+    /// A short summary of the route
+    pub summary: Option<String>,
+    /// A longer description of the route
+    pub description: Option<String>,
+    /// Tags used to group routes
+    pub tags: Vec<String>,
+    /// The request body content type and schema
+    pub request_body: Option<(String, String)>,
+    /// The documented responses, as status, description, and optional content type and schema
+    pub responses: Vec<(u16, String, Option<(String, String)>)>,
+}
+
+impl RouteDoc {
+    ///
+    pub fn new() -> Self {
+        RouteDoc::default()
+    }
+
+    /// Set the route summary
+    pub fn summary(mut self, summary: &str) -> Self {
+        self.summary = Some(summary.to_string());
+        self
+    }
+
+    /// Set the route description
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Add a tag to the route
+    pub fn tag(mut self, tag: &str) -> Self {
+        self.tags.push(tag.to_string());
+        self
+    }
+
+    /// Document the request body
+    pub fn request_body(mut self, content_type: &str, schema: &str) -> Self {
+        self.request_body = Some((content_type.to_string(), schema.to_string()));
+        self
+    }
+
+    /// Document a response without body
+    pub fn response(mut self, status: u16, description: &str) -> Self {
+        self.responses.push((status, description.to_string(), None));
+        self
+    }
+
+    /// Document a response with a body
+    pub fn response_with_body(mut self, status: u16, description: &str, content_type: &str, schema: &str) -> Self {
+        self.responses.push((status, description.to_string(), Some((content_type.to_string(), schema.to_string()))));
+        self
+    }
 }
 
 ///
@@ -88,28 +227,58 @@ impl<'a> IntoIterator for &'a RequestGuardCollection {
 }
 
 /// A trait to provide an other layer of validation before allowing a request into a controller
-pub trait RequestGuard {
+pub trait RequestGuard: Send + Sync {
     ///
     fn validate(&self, req: &mut SyncRequest, res: &mut SyncResponse) -> RequestContinuation;
+
+    /// Name of the guard used for route introspection, defaults to the type name
+    fn name(&self) -> &str {
+        ::std::any::type_name::<Self>()
+    }
 }
 
-type DelegateFunction<T> = Fn(&T, &SyncRequest, &mut SyncResponse);
-type ControllerDelegate<T> = (Method, UriPathMatcher, Option<RequestGuardCollection>, Box<DelegateFunction<T>>);
+type DelegateFunction<T> = Fn(&T, &SyncRequest, &mut SyncResponse) + Send + Sync;
+
+/// A delegate registered on a controller builder, its path is parsed when building the controller
+struct PendingDelegate<T> {
+    method: Method,
+    path: String,
+    guards: Option<RequestGuardCollection>,
+    name: Option<String>,
+    doc: Option<RouteDoc>,
+    consumes: Vec<String>,
+    produces: Vec<String>,
+    func: Box<DelegateFunction<T>>,
+}
 
-/// Struct to delegate a request to a registered function matching booth a `method` and a `path`
-pub struct ControllerDispatch<T> {
-    /// The context sent with the request to the function
-    delegate_context: T,
-    /// List of delegates
-    delegates: RwLock<Vec<ControllerDelegate<T>>>,
+struct ControllerDelegate<T> {
+    method: Method,
+    path: UriPathMatcher,
+    guards: Option<RequestGuardCollection>,
+    name: Option<String>,
+    doc: Option<RouteDoc>,
+    consumes: Vec<Mime>,
+    produces: Vec<Mime>,
+    func: Box<DelegateFunction<T>>,
 }
 
-impl<T: Send + Sync> ControllerDispatch<T> {
-    ///
-    pub fn new(delegate_context: T) -> Self {
-        ControllerDispatch {
-            delegate_context,
-            delegates: RwLock::new(Vec::new()),
+/// Builder for a `BasicController`. Delegates are registered on the builder, and the resulting controller route table is immutable,
+/// so no lock is taken while dispatching requests.
+pub struct Builder<C> {
+    base_path: String,
+    controller_context: C,
+    delegates: Vec<PendingDelegate<C>>,
+    misplaced_annotation: bool,
+}
+
+impl<C: Send + Sync> Builder<C> {
+    /// Create a new controller builder
+    pub fn new(name: &str, controller_context: C) -> Self {
+        Builder {
+            base_path: name.to_string(),
+            controller_context,
+            delegates: Vec::new(),
+            misplaced_annotation: false,
         }
     }
 
@@ -118,12 +287,15 @@ impl<T: Send + Sync> ControllerDispatch<T> {
     ///
     /// ```rust,no_run
     /// let u8_context = 1;
-    /// let dispatch = ControllerDispatch::new(u8_context);
-    /// dispatch.add(Method::Get, "^/test$", |ctx, req, res| { println!("this will handle Get request done on <your_host>/test")});
+    /// let u8_controller = BasicController::builder("/test", u8_context)
+    ///     .add(Method::GET, "/", |ctx, req, res| { println!("this will handle Get request done on <your_host>/test/")})
+    ///     .build()
+    ///     .expect("Invalid routes");
     /// ```
-    pub fn add<F>(&self, method: Method, path: &str, delegate_func: F)
-        where for<'r, 's, 't0> F: 'static + Fn(&'r T, &'s SyncRequest, &'t0 mut SyncResponse) {
-        self.delegates.write().push((method, UriPathMatcher::new(path).expect("Unable to add delegate, path is invalid"), None, Box::new(delegate_func)));
+    pub fn add<F>(mut self, method: Method, path: &str, delegate_func: F) -> Self
+        where for<'r, 's, 't0> F: 'static + Send + Sync + Fn(&'r C, &'s SyncRequest, &'t0 mut SyncResponse) {
+        self.delegates.push(PendingDelegate { method, path: path.to_string(), guards: None, name: None, doc: None, consumes: Vec::new(), produces: Vec::new(), func: Box::new(delegate_func) });
+        self
     }
 
     /// Add a delegate function to handle a particular request
@@ -132,22 +304,206 @@ impl<T: Send + Sync> ControllerDispatch<T> {
     /// ```rust,no_run
     /// let u8_context = 1;
     /// let guard = BodyGuard;
-    /// let dispatch = ControllerDispatch::new(u8_context);
-    /// dispatch.add_with_guards(Method::Get, "^/test$", guard.into(), |ctx, req, res| { println!("this will handle Get request done on <your_host>/test")});
+    /// let u8_controller = BasicController::builder("/test", u8_context)
+    ///     .add_with_guards(Method::PUT, "/", guard.into(), |ctx, req, res| { println!("this will handle Put request done on <your_host>/test/")})
+    ///     .build()
+    ///     .expect("Invalid routes");
+    /// ```
+    pub fn add_with_guards<F>(mut self, method: Method, path: &str, guards: RequestGuardCollection, delegate_func: F) -> Self
+        where for<'r, 's, 't0> F: 'static + Send + Sync + Fn(&'r C, &'s SyncRequest, &'t0 mut SyncResponse) {
+        self.delegates.push(PendingDelegate { method, path: path.to_string(), guards: Some(guards), name: None, doc: None, consumes: Vec::new(), produces: Vec::new(), func: Box::new(delegate_func) });
+        self
+    }
+
+    /// Name the last added delegate, so that urls targeting it can be generated with `Router::url_for` or `SyncRequest::url_for`.
+    /// Calling this method before adding any delegate makes the build fail.
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let u8_controller = BasicController::builder("/users", 1u8)
+    ///     .add(Method::GET, "/<id#r(^[0-9]+$)>", |ctx, req, res| { println!("this will handle Get request done on <your_host>/users/<id>")})
+    ///     .with_name("read_user")
+    ///     .build()
+    ///     .expect("Invalid routes");
+    /// ```
+    pub fn with_name(mut self, name: &str) -> Self {
+        if let Some(delegate) = self.delegates.last_mut() {
+            delegate.name = Some(name.to_string());
+        } else {
+            self.misplaced_annotation = true;
+        }
+        self
+    }
+
+    /// Attach documentation to the last added delegate, used when generating an OpenAPI document.
+    /// Calling this method before adding any delegate makes the build fail.
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let u8_controller = BasicController::builder("/users", 1u8)
+    ///     .add(Method::GET, "/<id#r(^[0-9]+$)>", |ctx, req, res| { println!("this will handle Get request done on <your_host>/users/<id>")})
+    ///     .with_doc(RouteDoc::new().summary("Read a user").response_with_body(200, "The user", "application/json", r#"{"type": "object"}"#))
+    ///     .build()
+    ///     .expect("Invalid routes");
+    /// ```
+    pub fn with_doc(mut self, doc: RouteDoc) -> Self {
+        if let Some(delegate) = self.delegates.last_mut() {
+            delegate.doc = Some(doc);
+        } else {
+            self.misplaced_annotation = true;
+        }
+        self
+    }
+
+    /// Restrict the last added delegate to requests whose `Content-Type` matches `media_type`, e.g. `application/json` or
+    /// `text/*`. Can be called many times to accept many media types. Requests matching the path of such delegates but
+    /// none of their media types are answered with `415 Unsupported Media Type`.
+    /// Calling this method before adding any delegate makes the build fail.
+    pub fn consumes(mut self, media_type: &str) -> Self {
+        if let Some(delegate) = self.delegates.last_mut() {
+            delegate.consumes.push(media_type.to_string());
+        } else {
+            self.misplaced_annotation = true;
+        }
+        self
+    }
+
+    /// Declare that the last added delegate produces `media_type`, e.g. `text/csv`. Can be called many times for a delegate
+    /// producing many media types. When many delegates match a request, the one producing the media type preferred by the
+    /// `Accept` header is chosen, and requests accepting none of them are answered with `406 Not Acceptable`.
+    /// Calling this method before adding any delegate makes the build fail.
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let reports = BasicController::builder("/reports", ())
+    ///     .add(Method::GET, "/", |_, _, res| { res.status(StatusCode::OK).header("Content-Type", "application/json"); })
+    ///     .produces("application/json")
+    ///     .add(Method::GET, "/", |_, _, res| { res.status(StatusCode::OK).header("Content-Type", "text/csv"); })
+    ///     .produces("text/csv")
+    ///     .build()
+    ///     .expect("Invalid routes");
+    /// ```
+    pub fn produces(mut self, media_type: &str) -> Self {
+        if let Some(delegate) = self.delegates.last_mut() {
+            delegate.produces.push(media_type.to_string());
+        } else {
+            self.misplaced_annotation = true;
+        }
+        self
+    }
+
+    /// Builds the controller, validating every registered path. Fails on the first invalid path or on two delegates
+    /// registered for the same method and path.
+    pub fn build(self) -> Result<BasicController<C>, RouteError> {
+        let Builder {
+            base_path,
+            controller_context,
+            delegates: pending,
+            misplaced_annotation,
+        } = self;
+
+        if misplaced_annotation {
+            return Err(RouteError::MisplacedAnnotation);
+        }
+
+        let mut delegates: Vec<ControllerDelegate<C>> = Vec::with_capacity(pending.len());
+        let base_has_tail = UriPathMatcher::new(&base_path).map(|p| p.has_tail()).unwrap_or(false);
+
+        for PendingDelegate { method, path, guards, name, doc, consumes, produces, func } in pending {
+            let mut u_p_m = UriPathMatcher::new(&path).map_err(|reason| RouteError::InvalidPath { path: path.clone(), reason })?;
+
+            // The trailing slash belongs to the whole route, so the delegate `/` of a controller `/users` is `/users/` while
+            // the delegate `` is `/users`. A controller ending with a tail segment captures the trailing slash instead.
+            let joined = format!("{}{}", base_path.trim_end_matches('/'), path);
+            u_p_m.set_trailing_slash(!base_has_tail && joined.len() > 1 && joined.ends_with('/'));
+
+            let consumes = parse_media_types(consumes)?;
+            let produces = parse_media_types(produces)?;
+
+            // Delegates sharing a path are told apart by their media types
+            if delegates.iter().any(|d| d.method == method && d.path.conflicts_with(&u_p_m)
+                && media_types_overlap(&d.consumes, &consumes) && media_types_overlap(&d.produces, &produces)) {
+                return Err(RouteError::DuplicateRoute { method, path });
+            }
+
+            if let Some(ref name) = name {
+                if delegates.iter().any(|d| d.name.as_ref() == Some(name)) {
+                    return Err(RouteError::DuplicateName { name: name.clone() });
+                }
+            }
+
+            delegates.push(ControllerDelegate { method, path: u_p_m, guards, name, doc, consumes, produces, func });
+        }
+
+        // Routes ending with a tail segment are tried last, the longest first
+        delegates.sort_by_key(|d| tail_precedence(&d.path));
+
+        Ok(BasicController {
+            base_path,
+            dispatch: ControllerDispatch {
+                delegate_context: controller_context,
+                delegates,
+            },
+        })
+    }
+}
+
+/// Migration helpers for controller contexts which are `Send` but not `Sync`, e.g. a context holding a `Cell` or a `RefCell`.
+/// The context is wrapped into a `Mutex`, meaning the requests handled by such a controller are processed one at a time.
+/// Prefer using thread-safe types (`Atomic*`, `RwLock`, `Mutex`) for the mutable parts of the context when possible.
+impl<C: Send> Builder<Mutex<C>> {
+    /// Create a controller builder with a context that is not `Sync`
+    pub fn new_locked(name: &str, controller_context: C) -> Self {
+        Builder::new(name, Mutex::new(controller_context))
+    }
+
+    /// Add a delegate function receiving the locked context
+    /// # Example
+    ///
+    /// ```rust,no_run
+    /// let u8_controller = BasicController::builder_locked("/test", std::cell::Cell::new(1u8))
+    ///     .add_locked(Method::GET, "/", |ctx, req, res| { ctx.set(ctx.get() + 1) })
+    ///     .build()
+    ///     .expect("Invalid routes");
     /// ```
-    pub fn add_with_guards<F>(&self, method: Method, path: &str, guards: RequestGuardCollection, delegate_func: F)
-        where for<'r, 's, 't0> F: 'static + Fn(&'r T, &'s SyncRequest, &'t0 mut SyncResponse) {
-        self.delegates.write().push((method, UriPathMatcher::new(path).expect("Unable to add delegate, path is invalid"), Some(guards), Box::new(delegate_func)));
+    pub fn add_locked<F>(self, method: Method, path: &str, delegate_func: F) -> Self
+        where for<'r, 's, 't0> F: 'static + Send + Sync + Fn(&'r C, &'s SyncRequest, &'t0 mut SyncResponse) {
+        self.add(method, path, move |ctx: &Mutex<C>, req: &SyncRequest, res: &mut SyncResponse| delegate_func(&ctx.lock(), req, res))
+    }
+
+    /// Add a delegate function receiving the locked context, with guards
+    pub fn add_locked_with_guards<F>(self, method: Method, path: &str, guards: RequestGuardCollection, delegate_func: F) -> Self
+        where for<'r, 's, 't0> F: 'static + Send + Sync + Fn(&'r C, &'s SyncRequest, &'t0 mut SyncResponse) {
+        self.add_with_guards(method, path, guards, move |ctx: &Mutex<C>, req: &SyncRequest, res: &mut SyncResponse| delegate_func(&ctx.lock(), req, res))
+    }
+}
+
+/// Sort key giving routes without tail segment precedence over routes with one, and longer tail routes precedence over shorter ones.
+/// The sort being stable, registration order is kept otherwise.
+pub(crate) fn tail_precedence(path: &UriPathMatcher) -> (bool, usize) {
+    if path.has_tail() {
+        (true, usize::max_value() - path.len())
+    } else {
+        (false, 0)
     }
+}
 
+/// Struct to delegate a request to a registered function matching booth a `method` and a `path`
+pub(crate) struct ControllerDispatch<T> {
+    /// The context sent with the request to the function
+    delegate_context: T,
+    /// List of delegates
+    delegates: Vec<ControllerDelegate<T>>,
+}
+
+impl<T: Send + Sync> ControllerDispatch<T> {
     ///
-    pub fn dispatch(&self, req: &mut SyncRequest, res: &mut SyncResponse) {
+    pub(crate) fn dispatch(&self, req: &mut SyncRequest, res: &mut SyncResponse) {
         use std::iter::FromIterator;
-        let delegates_list = self.delegates.read();
         let method = req.method();
 
-        let retained_delegate = Vec::from_iter(delegates_list.iter().filter(|x| {
-            x.0 == method
+        let retained_delegate = Vec::from_iter(self.delegates.iter().filter(|x| {
+            x.method == method
         }));
 
         if retained_delegate.len() == 0 {
@@ -155,30 +511,102 @@ impl<T: Send + Sync> ControllerDispatch<T> {
             return;
         }
 
-        for del in retained_delegate {
-            let (_, ref u_p_m, ref op_guards, ref boxed_func) = del;
+        let path_delegates = Vec::from_iter(retained_delegate.into_iter().filter(|x| req.current_path_matches_all(&x.path)));
 
-            if req.current_path_match_all(u_p_m) {
-                if let Some(ref guards) = op_guards {
-                    for guard in guards {
-                        use crate::RequestContinuation::*;
-                        if let Stop = guard.validate(req, res) {
-                            return;
-                        }
-                    }
+        if path_delegates.len() == 0 {
+            res.status(StatusCode::NOT_FOUND);
+            return;
+        }
+
+        let content_type = req.headers_map().get(header::CONTENT_TYPE).and_then(|v| header::ContentType::parse_header(&v).ok()).map(|c| c.0);
+        let consumable_delegates = Vec::from_iter(path_delegates.into_iter().filter(|x| {
+            x.consumes.is_empty() || content_type.as_ref().map(|ct| x.consumes.iter().any(|m| media_type_matches(m, ct))).unwrap_or(false)
+        }));
+
+        if consumable_delegates.len() == 0 {
+            res.status(StatusCode::UNSUPPORTED_MEDIA_TYPE);
+            return;
+        }
+
+        // A missing, empty or malformed `Accept` header accepts any media type
+        let accept = header::Accept::parse_header(&req.headers_map().get_all(header::ACCEPT)).ok().map(|a| a.0).filter(|a| !a.is_empty());
+
+        // The delegate producing the media type preferred by the client is chosen, the first registered one on equal preference
+        let mut chosen: Option<(&ControllerDelegate<T>, (Quality, bool))> = None;
+        for del in consumable_delegates {
+            if let Some(preference) = produced_preference(&del.produces, accept.as_ref().map(|a| a.as_slice())) {
+                if chosen.as_ref().map(|&(_, best)| preference > best).unwrap_or(true) {
+                    chosen = Some((del, preference));
                 }
-                boxed_func(&self.delegate_context, req, res);
+            }
+        }
+
+        let del = match chosen {
+            Some((del, _)) => del,
+            None => {
+                res.status(StatusCode::NOT_ACCEPTABLE);
                 return;
             }
+        };
+
+        let ControllerDelegate { path: ref u_p_m, guards: ref op_guards, func: ref boxed_func, .. } = *del;
+
+        req.current_path_match_all(u_p_m);
+
+        if let Some(ref guards) = op_guards {
+            for guard in guards {
+                use crate::RequestContinuation::*;
+                if let Stop = guard.validate(req, res) {
+                    return;
+                }
+            }
         }
 
-        res.status(StatusCode::NOT_FOUND);
+        boxed_func(&self.delegate_context, req, res);
     }
 }
 
-unsafe impl<T> Sync for ControllerDispatch<T> {}
+fn parse_media_types(media_types: Vec<String>) -> Result<Vec<Mime>, RouteError> {
+    media_types.into_iter().map(|m| m.parse::<Mime>().map_err(|_| RouteError::InvalidMediaType { media_type: m })).collect()
+}
 
-unsafe impl<T> Send for ControllerDispatch<T> {}
+/// Whether `media_type` is within `range`, e.g. `text/csv` is within `text/*`
+fn media_type_matches(range: &Mime, media_type: &Mime) -> bool {
+    (range.type_() == mime::STAR || range.type_() == media_type.type_())
+        && (range.subtype() == mime::STAR || range.subtype() == media_type.subtype())
+}
+
+/// Whether a request could be handled by two delegates with those media types, an empty list meaning any media type
+fn media_types_overlap(a: &[Mime], b: &[Mime]) -> bool {
+    a.is_empty() || b.is_empty() || a.iter().any(|m_a| b.iter().any(|m_b| media_type_matches(m_a, m_b) || media_type_matches(m_b, m_a)))
+}
+
+/// The quality the `Accept` header gives to `media_type`, taken from the most specific matching media range
+fn accepted_quality(accept: &[QualityItem<Mime>], media_type: &Mime) -> Option<Quality> {
+    accept.iter()
+        .filter(|item| media_type_matches(&item.item, media_type))
+        .max_by_key(|item| (item.item.type_() != mime::STAR) as u8 + (item.item.subtype() != mime::STAR) as u8)
+        .map(|item| item.quality)
+}
+
+/// How much the client prefers the response of a delegate producing `produces`, `None` when it is not acceptable.
+/// Without an `Accept` list every delegate is acceptable.
+/// Delegates declaring their media types are preferred over the others on equal quality.
+fn produced_preference(produces: &[Mime], accept: Option<&[QualityItem<Mime>]>) -> Option<(Quality, bool)> {
+    let accept = match accept {
+        Some(accept) => accept,
+        None => return Some((Quality::default(), !produces.is_empty())),
+    };
+
+    let quality = if produces.is_empty() {
+        // The delegate can produce any media type, it gets the one preferred by the client
+        accept.iter().map(|item| item.quality).max()
+    } else {
+        produces.iter().filter_map(|m| accepted_quality(accept, m)).max()
+    };
+
+    quality.filter(|quality| *quality > header::q(0u16)).map(|quality| (quality, !produces.is_empty()))
+}
 
 /// An helper struct embedding a `ControllerDispatch`.
 pub struct BasicController<C> {
@@ -194,41 +622,39 @@ impl<C: Send + Sync> Controller for BasicController<C> {
     fn base_path(&self) -> &str {
         &self.base_path
     }
-}
 
-impl<C: Send + Sync> BasicController<C> {
-    ///
-    pub fn new(name: &str, controller_context: C) -> Self {
-        BasicController {
-            base_path: name.to_string(),
-            dispatch: ControllerDispatch::new(controller_context),
-        }
+    fn routes(&self) -> Vec<RouteInfo> {
+        self.dispatch.delegates.iter().map(|d| {
+            RouteInfo {
+                method: d.method.clone(),
+                path: d.path.to_string(),
+                segments: d.path.iter().map(RouteSegment::from).collect(),
+                guards: d.guards.iter().flat_map(|guards| guards.into_iter().map(|g| g.name().to_string())).collect(),
+                controller: self.base_path.clone(),
+                name: d.name.clone(),
+                doc: d.doc.clone(),
+                trailing_slash: d.path.trailing_slash(),
+                case_insensitive: d.path.case_insensitive(),
+                consumes: d.consumes.iter().map(|m| m.to_string()).collect(),
+                produces: d.produces.iter().map(|m| m.to_string()).collect(),
+                version: None,
+                host: None,
+            }
+        }).collect()
     }
+}
 
-    /// Add a delegate function to handle a particular request
-    /// # Example
-    ///
-    /// ```rust,no_run
-    /// let u8_context = 1;
-    /// let u8_controller = BasicController::new(u8_context);
-    /// u8_controller.add(Method::Get, "^/test$", |ctx, req, res| { println!("this will handle Get request done on <your_host>/test")});
-    /// ```
-    pub fn add<F>(&self, method: Method, path: &str, delegate_func: F)
-        where for<'r, 's, 't0> F: 'static + Fn(&'r C, &'s SyncRequest, &'t0 mut SyncResponse) {
-        self.dispatch.add(method, path, delegate_func);
+impl<C: Send + Sync> BasicController<C> {
+    /// Create a new controller builder
+    pub fn builder(name: &str, controller_context: C) -> Builder<C> {
+        Builder::new(name, controller_context)
     }
+}
 
-    /// Add a delegate function to handle a particular request
-    /// # Example
-    ///
-    /// ```rust,no_run
-    /// let u8_context = 1;
-    /// let u8_controller = BasicController::new(u8_context);
-    /// u8_controller.add(Method::Get, "^/test$", |ctx, req, res| { println!("this will handle Get request done on <your_host>/test")});
-    /// ```
-    pub fn add_with_guards<F>(&self, method: Method, path: &str, guards: RequestGuardCollection, delegate_func: F)
-        where for<'r, 's, 't0> F: 'static + Fn(&'r C, &'s SyncRequest, &'t0 mut SyncResponse) {
-        self.dispatch.add_with_guards(method, path, guards, delegate_func);
+impl<C: Send> BasicController<Mutex<C>> {
+    /// Create a new controller builder with a context that is not `Sync`, see `Builder::new_locked`
+    pub fn builder_locked(name: &str, controller_context: C) -> Builder<Mutex<C>> {
+        Builder::new_locked(name, controller_context)
     }
 }
 
@@ -245,3 +671,38 @@ impl RequestGuard for BodyGuard {
     }
 }
 
+
+/// RequestGuard ensuring that a request state entry of type `T` was provided, typically by a middleware.
+/// The request is stopped with a `500 Internal Server Error` when missing, unless an other status is specified.
+pub struct StateGuard<T> {
+    status: StatusCode,
+    _state: PhantomData<fn() -> T>,
+}
+
+impl<T: Send + Sync + 'static> StateGuard<T> {
+    ///
+    pub fn new() -> Self {
+        StateGuard {
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            _state: PhantomData,
+        }
+    }
+
+    /// Set the status returned when the state entry is missing, e.g. `401 Unauthorized` for an authentication state
+    pub fn with_status(mut self, status: StatusCode) -> Self {
+        self.status = status;
+        self
+    }
+}
+
+impl<T: Send + Sync + 'static> RequestGuard for StateGuard<T> {
+    fn validate(&self, req: &mut SyncRequest, res: &mut SyncResponse) -> RequestContinuation {
+        if let Err(e) = req.require_state::<T>() {
+            warn!("{}", e);
+            res.status(self.status);
+            return RequestContinuation::Stop
+        }
+
+        RequestContinuation::Continue
+    }
+}
@@ -0,0 +1,172 @@
+use std::str::FromStr;
+use std::sync::Arc;
+
+use cookie::CookieJar as RawJar;
+
+pub use cookie::{Cookie, CookieBuilder, Key, SameSite};
+/// The duration of the `Max-Age` attribute of a cookie
+pub use time::Duration;
+
+use crate::error::CookieError;
+use crate::http::*;
+
+/// The keys signing and encrypting the cookies, see `server::Builder::cookie_keys`.
+/// Cookies are signed and encrypted with the current key, and verified and decrypted with the current key or any of
+/// the previous ones, so a key can be rotated without invalidating the cookies already issued.
+/// # Example
+/// ```rust,no_run
+/// let keys = CookieKeys::new(current_secret.as_bytes())?.previous(former_secret.as_bytes())?;
+/// let server = Server::builder().cookie_keys(keys).build();
+/// ```
+pub struct CookieKeys {
+    keys: Vec<Key>,
+}
+
+impl CookieKeys {
+    /// Derive the current key from a master secret of at least 32 bytes, a shorter secret is refused
+    pub fn new(master: &[u8]) -> Result<Self, CookieError> {
+        Ok(CookieKeys {
+            keys: vec![derive_key(master)?],
+        })
+    }
+
+    /// Generate a random current key, the cookies issued with it are then only valid until the server restarts
+    pub fn generate() -> Self {
+        CookieKeys {
+            keys: vec![Key::generate()],
+        }
+    }
+
+    /// Derive a previous key from a master secret of at least 32 bytes, still accepted for the cookies issued with it.
+    /// A shorter secret is refused.
+    pub fn previous(mut self, master: &[u8]) -> Result<Self, CookieError> {
+        self.keys.push(derive_key(master)?);
+        Ok(self)
+    }
+
+    fn current(&self) -> &Key {
+        &self.keys[0]
+    }
+
+    /// Sign a cookie with the current key
+    pub(crate) fn sign(&self, cookie: Cookie<'static>) -> Cookie<'static> {
+        let name = cookie.name().to_string();
+        let mut jar = RawJar::new();
+        jar.signed(self.current()).add(cookie);
+        jar.get(&name).cloned().expect("The signed cookie was just added")
+    }
+
+    /// Encrypt and sign a cookie with the current key
+    pub(crate) fn encrypt(&self, cookie: Cookie<'static>) -> Cookie<'static> {
+        let name = cookie.name().to_string();
+        let mut jar = RawJar::new();
+        jar.private(self.current()).add(cookie);
+        jar.get(&name).cloned().expect("The private cookie was just added")
+    }
+
+    fn verify(&self, cookie: &Cookie<'static>) -> Option<Cookie<'static>> {
+        self.keys.iter().filter_map(|key| {
+            let mut jar = RawJar::new();
+            jar.add_original(cookie.clone());
+            let verified = jar.signed(key).get(cookie.name());
+            verified
+        }).next()
+    }
+
+    fn decrypt(&self, cookie: &Cookie<'static>) -> Option<Cookie<'static>> {
+        self.keys.iter().filter_map(|key| {
+            let mut jar = RawJar::new();
+            jar.add_original(cookie.clone());
+            let decrypted = jar.private(key).get(cookie.name());
+            decrypted
+        }).next()
+    }
+}
+
+/// The minimum length in bytes of the master secret of a cookie key
+const MIN_MASTER_LEN: usize = 32;
+
+fn derive_key(master: &[u8]) -> Result<Key, CookieError> {
+    if master.len() < MIN_MASTER_LEN {
+        return Err(CookieError::KeyTooShort(MIN_MASTER_LEN));
+    }
+
+    Ok(Key::from_master(master))
+}
+
+/// The cookies sent with a request, see `SyncRequest::cookies`
+#[derive(Clone, Default)]
+pub struct CookieJar {
+    jar: RawJar,
+    keys: Option<Arc<CookieKeys>>,
+}
+
+impl ::std::fmt::Debug for CookieJar {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        f.debug_list().entries(self.jar.iter()).finish()
+    }
+}
+
+impl CookieJar {
+    /// Parse the cookies of the `Cookie` headers, ignoring the malformed ones
+    pub(crate) fn from_headers(headers: &header::HeaderMap) -> Self {
+        let mut jar = RawJar::new();
+        for value in headers.get_all(header::COOKIE).iter() {
+            let value = match value.to_str() {
+                Ok(value) => value,
+                Err(_) => continue,
+            };
+
+            for pair in value.split(';').map(|p| p.trim()).filter(|p| !p.is_empty()) {
+                if let Ok(cookie) = Cookie::parse(pair.to_string()) {
+                    jar.add_original(cookie);
+                }
+            }
+        }
+
+        CookieJar {
+            jar,
+            keys: None,
+        }
+    }
+
+    pub(crate) fn set_keys(&mut self, keys: Option<Arc<CookieKeys>>) {
+        self.keys = keys;
+    }
+
+    /// Returns the cookie named `name`, if any
+    pub fn get(&self, name: &str) -> Option<&Cookie<'static>> {
+        self.jar.get(name)
+    }
+
+    /// Returns the value of the cookie named `name` parsed as a `T`, none if there is no such cookie or its value
+    /// does not parse
+    pub fn value<T: FromStr>(&self, name: &str) -> Option<T> {
+        self.jar.get(name).and_then(|c| c.value().parse::<T>().ok())
+    }
+
+    /// Returns the cookie named `name` once its signature is verified, none if there is no such cookie, no cookie keys
+    /// are configured or the signature is invalid
+    pub fn get_signed(&self, name: &str) -> Option<Cookie<'static>> {
+        self.keys.as_ref()?.verify(self.jar.get(name)?)
+    }
+
+    /// Returns the cookie named `name` once decrypted, none if there is no such cookie, no cookie keys are configured
+    /// or it cannot be decrypted
+    pub fn get_private(&self, name: &str) -> Option<Cookie<'static>> {
+        self.keys.as_ref()?.decrypt(self.jar.get(name)?)
+    }
+
+    /// Returns an iterator over the cookies, as received
+    pub fn iter(&self) -> impl Iterator<Item=&Cookie<'static>> {
+        self.jar.iter()
+    }
+}
+
+/// A cookie removing the cookie of the same name, path and domain from the client
+pub(crate) fn removal(mut cookie: Cookie<'static>) -> Cookie<'static> {
+    cookie.set_value("");
+    cookie.set_max_age(Duration::seconds(0));
+    cookie.set_expires(time::now() - Duration::days(365));
+    cookie
+}
@@ -0,0 +1,285 @@
+use regex::Regex;
+
+use crate::http::*;
+use crate::middleware::Middleware;
+use crate::utils::{RequestContinuation, ToRegex};
+
+/// How the origin of a cross-origin request is allowed
+enum AllowedOrigin {
+    Exact(String),
+    Regex(Regex),
+    Predicate(Box<Fn(&str) -> bool + Send + Sync>),
+}
+
+impl AllowedOrigin {
+    fn allows(&self, origin: &str) -> bool {
+        match *self {
+            AllowedOrigin::Exact(ref o) => o.eq_ignore_ascii_case(origin),
+            AllowedOrigin::Regex(ref r) => r.is_match(origin),
+            AllowedOrigin::Predicate(ref p) => p(origin),
+        }
+    }
+}
+
+/// A builder of the CORS middleware
+pub struct Builder {
+    any_origin: bool,
+    origins: Vec<AllowedOrigin>,
+    methods: Option<Vec<Method>>,
+    any_header: bool,
+    headers: Vec<String>,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<u64>,
+}
+
+impl Builder {
+    /// Create a new CORS builder allowing no origin
+    pub fn new() -> Self {
+        Builder {
+            any_origin: false,
+            origins: Vec::new(),
+            methods: None,
+            any_header: false,
+            headers: Vec::new(),
+            exposed_headers: Vec::new(),
+            credentials: false,
+            max_age: None,
+        }
+    }
+
+    /// Allow requests from any origin
+    pub fn allow_any_origin(mut self) -> Self {
+        self.any_origin = true;
+        self
+    }
+
+    /// Allow requests from an origin, e.g. `https://example.com`
+    pub fn allow_origin(mut self, origin: &str) -> Self {
+        self.origins.push(AllowedOrigin::Exact(origin.trim_end_matches('/').to_string()));
+        self
+    }
+
+    /// Allow requests from the origins matching a regex, e.g. `^https://[a-z]+\.example\.com$`
+    pub fn allow_origin_regex<R: ToRegex>(mut self, regex: R) -> Self {
+        self.origins.push(AllowedOrigin::Regex(regex.to_regex().expect("Unable to construct origin regex")));
+        self
+    }
+
+    /// Allow requests from the origins for which the predicate returns `true`
+    pub fn allow_origin_fn<F>(mut self, predicate: F) -> Self where F: Fn(&str) -> bool + Send + Sync + 'static {
+        self.origins.push(AllowedOrigin::Predicate(Box::new(predicate)));
+        self
+    }
+
+    /// Restrict the methods allowed by preflight requests, which are otherwise the methods of the routes matching the
+    /// requested path
+    pub fn allow_methods(mut self, methods: &[Method]) -> Self {
+        self.methods = Some(methods.to_vec());
+        self
+    }
+
+    /// Allow requests with a header besides the CORS-safelisted ones, e.g. `Authorization`
+    pub fn allow_header(mut self, header: &str) -> Self {
+        self.headers.push(header.to_lowercase());
+        self
+    }
+
+    /// Allow requests with any header
+    pub fn allow_any_header(mut self) -> Self {
+        self.any_header = true;
+        self
+    }
+
+    /// Let the browser expose a response header to the requesting script, besides the CORS-safelisted ones
+    pub fn expose_header(mut self, header: &str) -> Self {
+        self.exposed_headers.push(header.to_string());
+        self
+    }
+
+    /// Allow requests with credentials, i.e. cookies and authorization headers
+    pub fn allow_credentials(mut self) -> Self {
+        self.credentials = true;
+        self
+    }
+
+    /// Let the browser cache the preflight responses for `seconds`
+    pub fn max_age(mut self, seconds: u64) -> Self {
+        self.max_age = Some(seconds);
+        self
+    }
+
+    /// Build the CORS middleware
+    pub fn build(self) -> Cors {
+        let Builder {
+            any_origin,
+            origins,
+            methods,
+            any_header,
+            headers,
+            exposed_headers,
+            credentials,
+            max_age,
+        } = self;
+
+        Cors {
+            any_origin,
+            origins,
+            methods,
+            any_header,
+            headers,
+            exposed_headers,
+            credentials,
+            max_age,
+        }
+    }
+}
+
+/// A middleware handling cross-origin requests. Preflight requests are answered with the methods of the routes
+/// matching the requested path, and the responses of allowed cross-origin requests are annotated with the CORS headers.
+/// Requests from an origin which is not allowed are handled without any CORS header, so the browser rejects them.
+/// # Example
+/// ```rust,no_run
+/// let cors = Cors::builder()
+///     .allow_origin("https://app.example.com")
+///     .allow_origin_regex(r"^https://[a-z]+\.preview\.example\.com$")
+///     .allow_header("Authorization")
+///     .allow_credentials()
+///     .max_age(3600)
+///     .build();
+///
+/// let middlewares = middleware::Builder::new().apply(cors, vec!["/api"], None).build();
+/// ```
+pub struct Cors {
+    any_origin: bool,
+    origins: Vec<AllowedOrigin>,
+    methods: Option<Vec<Method>>,
+    any_header: bool,
+    headers: Vec<String>,
+    exposed_headers: Vec<String>,
+    credentials: bool,
+    max_age: Option<u64>,
+}
+
+impl Cors {
+    /// Create a new CORS middleware builder
+    pub fn builder() -> Builder {
+        Builder::new()
+    }
+
+    fn allows(&self, origin: &str) -> bool {
+        self.any_origin || self.origins.iter().any(|o| o.allows(origin))
+    }
+
+    /// Whether the response depends on the origin of the request, i.e. the allowed origin is not `*`
+    fn varies_by_origin(&self) -> bool {
+        !self.any_origin || self.credentials
+    }
+
+    /// The allowed origin as sent back, the wildcard only being usable without credentials
+    fn allowed_origin<'a>(&self, origin: &'a str) -> &'a str {
+        if self.varies_by_origin() {
+            origin
+        } else {
+            "*"
+        }
+    }
+
+    fn preflight(&self, req: &SyncRequest, res: &mut SyncResponse, origin: &str) {
+        let requested_method = req.headers_map().get(header::ACCESS_CONTROL_REQUEST_METHOD)
+            .and_then(|m| m.to_str().ok())
+            .and_then(|m| m.parse::<Method>().ok());
+        let requested_headers: Vec<String> = req.headers_map().get_all(header::ACCESS_CONTROL_REQUEST_HEADERS).iter()
+            .flat_map(|h| h.to_str().unwrap_or("").split(',').map(|h| h.trim().to_lowercase()).collect::<Vec<String>>())
+            .filter(|h| !h.is_empty())
+            .collect();
+
+        let route_methods = req.route_methods();
+        if route_methods.is_empty() {
+            res.status(StatusCode::NOT_FOUND);
+            return;
+        }
+
+        let methods: Vec<Method> = route_methods.into_iter()
+            .filter(|m| self.methods.as_ref().map_or(true, |allowed| allowed.contains(m)))
+            .collect();
+        let method_allowed = requested_method.map_or(false, |m| methods.contains(&m));
+        let headers_allowed = self.any_header || requested_headers.iter().all(|h| self.headers.contains(h));
+
+        res.header(header::VARY, "Origin")
+            .header(header::VARY, "Access-Control-Request-Method")
+            .header(header::VARY, "Access-Control-Request-Headers");
+
+        if !method_allowed || !headers_allowed {
+            res.status(StatusCode::FORBIDDEN);
+            return;
+        }
+
+        let methods: Vec<&str> = methods.iter().map(|m| m.as_str()).collect();
+        res.status(StatusCode::NO_CONTENT)
+            .header(header::ACCESS_CONTROL_ALLOW_ORIGIN, self.allowed_origin(origin))
+            .header(header::ACCESS_CONTROL_ALLOW_METHODS, methods.join(", ").as_str());
+
+        if !requested_headers.is_empty() {
+            res.header(header::ACCESS_CONTROL_ALLOW_HEADERS, requested_headers.join(", ").as_str());
+        }
+
+        if self.credentials {
+            res.header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+
+        if let Some(max_age) = self.max_age {
+            res.header(header::ACCESS_CONTROL_MAX_AGE, max_age.to_string().as_str());
+        }
+    }
+}
+
+impl Middleware for Cors {
+    fn resolve(&self, req: &mut SyncRequest, res: &mut SyncResponse) -> RequestContinuation {
+        let origin = match req.headers_map().get(header::ORIGIN).and_then(|o| o.to_str().ok()) {
+            Some(origin) => origin.to_string(),
+            None => {
+                // A cache must not serve this response to a cross-origin request, which would get the CORS headers
+                if self.varies_by_origin() {
+                    res.header(header::VARY, "Origin");
+                }
+                return RequestContinuation::Continue;
+            }
+        };
+
+        let preflight = req.method() == Method::OPTIONS && req.headers_map().contains_key(header::ACCESS_CONTROL_REQUEST_METHOD);
+
+        if !self.allows(&origin) {
+            if self.varies_by_origin() {
+                res.header(header::VARY, "Origin");
+            }
+
+            if preflight {
+                res.status(StatusCode::FORBIDDEN);
+                return RequestContinuation::Stop;
+            }
+
+            return RequestContinuation::Continue;
+        }
+
+        if preflight {
+            self.preflight(req, res, &origin);
+            return RequestContinuation::Stop;
+        }
+
+        res.header(header::ACCESS_CONTROL_ALLOW_ORIGIN, self.allowed_origin(&origin));
+        if self.varies_by_origin() {
+            res.header(header::VARY, "Origin");
+        }
+
+        if self.credentials {
+            res.header(header::ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+
+        if !self.exposed_headers.is_empty() {
+            res.header(header::ACCESS_CONTROL_EXPOSE_HEADERS, self.exposed_headers.join(", ").as_str());
+        }
+
+        RequestContinuation::Continue
+    }
+}
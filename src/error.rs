@@ -17,6 +17,8 @@ pub enum ServerError {
     IOError(::std::io::Error),
     /// Bad listener configuration
     BadListenerConfig,
+    /// The router configured on the server builder is invalid
+    InvalidRoutes(RouteError),
 }
 
 impl From<::std::net::AddrParseError> for ServerError {
@@ -43,6 +45,12 @@ impl From<crate::http_types::uri::InvalidUri> for ServerError {
     }
 }
 
+impl From<RouteError> for ServerError {
+    fn from(e: RouteError) -> Self {
+        ServerError::InvalidRoutes(e)
+    }
+}
+
 impl From<::std::io::Error> for ServerError {
     fn from(e: ::std::io::Error) -> Self {
         ServerError::IOError(e)
@@ -60,6 +68,7 @@ impl ::std::error::Error for ServerError {
             UnsupportedUriScheme => "Unsupported URI scheme",
             IOError(ref e) => e.description(),
             BadListenerConfig => "Bad listener configuration",
+            InvalidRoutes(ref e) => e.description(),
         }
     }
 }
@@ -75,6 +84,291 @@ impl ::std::fmt::Display for ServerError {
             UnsupportedUriScheme => write!(f, "Unsupported URI scheme"),
             IOError(ref e) => e.fmt(f),
             BadListenerConfig => write!(f, "Bad listener configuration"),
+            InvalidRoutes(ref e) => e.fmt(f),
+        }
+    }
+}
+/// Error returned when a required request or application state entry is missing
+#[derive(Debug, Clone)]
+pub struct MissingStateError {
+    type_name: &'static str,
+    application: bool,
+}
+
+impl MissingStateError {
+    ///
+    pub(crate) fn of<T: ?Sized>() -> Self {
+        MissingStateError {
+            type_name: ::std::any::type_name::<T>(),
+            application: false,
+        }
+    }
+
+    ///
+    pub(crate) fn of_app<T: ?Sized>() -> Self {
+        MissingStateError {
+            type_name: ::std::any::type_name::<T>(),
+            application: true,
+        }
+    }
+
+    /// Name of the missing state type
+    pub fn type_name(&self) -> &'static str {
+        self.type_name
+    }
+}
+
+impl ::std::error::Error for MissingStateError {
+    fn description(&self) -> &str {
+        "Missing request state"
+    }
+}
+
+impl ::std::fmt::Display for MissingStateError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result<> {
+        if self.application {
+            write!(f, "Missing application state of type `{}`, make sure it is registered with `server::Builder::with_state`", self.type_name)
+        } else {
+            write!(f, "Missing request state of type `{}`, make sure a middleware inserts it before the request reaches the handler", self.type_name)
+        }
+    }
+}
+
+/// Errors raised while building a route table
+#[derive(Debug, Clone)]
+pub enum RouteError {
+    /// A registered path could not be parsed
+    InvalidPath {
+        /// The invalid path
+        path: String,
+        /// Why the path is invalid
+        reason: String,
+    },
+    /// Two delegates are registered for the same method and path
+    DuplicateRoute {
+        /// The method of both delegates
+        method: crate::http::Method,
+        /// The path of the second delegate
+        path: String,
+    },
+    /// Two delegates are registered with the same name
+    DuplicateName {
+        /// The name of both delegates
+        name: String,
+    },
+    /// An annotation was applied before registering any delegate
+    MisplacedAnnotation,
+    /// A consumed or produced media type could not be parsed
+    InvalidMediaType {
+        /// The invalid media type
+        media_type: String,
+    },
+    /// A host pattern could not be parsed
+    InvalidHost {
+        /// The invalid host pattern
+        pattern: String,
+        /// Why the host pattern is invalid
+        reason: String,
+    },
+    /// The default host is not registered on the router
+    UnknownHost {
+        /// The default host pattern
+        pattern: String,
+    },
+    /// The default version is not registered on the router
+    UnknownVersion {
+        /// The default version
+        version: String,
+    },
+}
+
+impl ::std::error::Error for RouteError {
+    fn description(&self) -> &str {
+        use crate::RouteError::*;
+        match self {
+            InvalidPath { .. } => "Invalid route path",
+            DuplicateRoute { .. } => "Duplicate route",
+            DuplicateName { .. } => "Duplicate route name",
+            MisplacedAnnotation => "Misplaced route annotation",
+            InvalidMediaType { .. } => "Invalid media type",
+            InvalidHost { .. } => "Invalid host pattern",
+            UnknownHost { .. } => "Unknown default host",
+            UnknownVersion { .. } => "Unknown default version",
+        }
+    }
+}
+
+impl ::std::fmt::Display for RouteError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result<> {
+        use crate::RouteError::*;
+        match self {
+            InvalidPath { ref path, ref reason } => write!(f, "Invalid route path `{}`: {}", path, reason),
+            DuplicateRoute { ref method, ref path } => write!(f, "A delegate is already registered for {} {}", method, path),
+            DuplicateName { ref name } => write!(f, "A delegate is already named {}", name),
+            MisplacedAnnotation => write!(f, "A route annotation was applied before registering any delegate"),
+            InvalidMediaType { ref media_type } => write!(f, "Invalid media type `{}`", media_type),
+            InvalidHost { ref pattern, ref reason } => write!(f, "Invalid host pattern `{}`: {}", pattern, reason),
+            UnknownHost { ref pattern } => write!(f, "The default host {} is not registered", pattern),
+            UnknownVersion { ref version } => write!(f, "The default version {} is not registered", version),
+        }
+    }
+}
+
+/// Errors raised while generating an url from a route name
+#[derive(Debug, Clone)]
+pub enum UrlError {
+    /// No route is registered with this name
+    UnknownRoute(String),
+    /// No value was provided for a named segment of the route
+    MissingParameter {
+        /// The route name
+        route: String,
+        /// The segment name
+        name: String,
+    },
+    /// The value provided for a segment doesn't match the segment regex
+    InvalidParameter {
+        /// The route name
+        route: String,
+        /// The segment name
+        name: String,
+        /// The rejected value
+        value: String,
+    },
+    /// The route contains an unnamed variable segment, which cannot be filled
+    UnnamedSegment(String),
+    /// The request was not dispatched by a router, so no url can be generated from it
+    NoRouter,
+}
+
+impl ::std::error::Error for UrlError {
+    fn description(&self) -> &str {
+        use crate::UrlError::*;
+        match self {
+            UnknownRoute(_) => "Unknown route name",
+            MissingParameter { .. } => "Missing url parameter",
+            InvalidParameter { .. } => "Invalid url parameter",
+            UnnamedSegment(_) => "Unnamed route segment",
+            NoRouter => "No router",
+        }
+    }
+}
+
+impl ::std::fmt::Display for UrlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result<> {
+        use crate::UrlError::*;
+        match self {
+            UnknownRoute(ref route) => write!(f, "No route is named {}", route),
+            MissingParameter { ref route, ref name } => write!(f, "Missing value for segment {} of route {}", name, route),
+            InvalidParameter { ref route, ref name, ref value } => write!(f, "Value `{}` is invalid for segment {} of route {}", value, name, route),
+            UnnamedSegment(ref route) => write!(f, "Route {} contains an unnamed variable segment", route),
+            NoRouter => write!(f, "The request was not dispatched by a router"),
+        }
+    }
+}
+
+/// Errors raised while setting a signed or private cookie, see `SyncResponse::signed_cookie`
+#[derive(Debug, Clone)]
+pub enum CookieError {
+    /// No cookie keys are configured on the server, see `server::Builder::cookie_keys`
+    NoKeys,
+    /// The master secret of a cookie key is shorter than the given minimum length in bytes
+    KeyTooShort(usize),
+}
+
+impl ::std::error::Error for CookieError {
+    fn description(&self) -> &str {
+        match self {
+            CookieError::NoKeys => "No cookie keys",
+            CookieError::KeyTooShort(_) => "Cookie key too short",
+        }
+    }
+}
+
+impl ::std::fmt::Display for CookieError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result<> {
+        match self {
+            CookieError::NoKeys => write!(f, "No cookie keys are configured to sign or encrypt the cookie"),
+            CookieError::KeyTooShort(min) => write!(f, "The master secret of a cookie key must be at least {} bytes long", min),
         }
     }
-}
\ No newline at end of file
+}
+
+/// Errors raised by a session store, see `session::SessionStore`
+#[derive(Debug, Clone)]
+pub enum SessionError {
+    /// The store failed to load, save or remove a session
+    Store(String),
+}
+
+impl ::std::error::Error for SessionError {
+    fn description(&self) -> &str {
+        match self {
+            SessionError::Store(_) => "Session store error",
+        }
+    }
+}
+
+impl ::std::fmt::Display for SessionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result<> {
+        match self {
+            SessionError::Store(ref e) => write!(f, "Session store error: {}", e),
+        }
+    }
+}
+
+/// Errors raised while loading the body of a request, see `BodyLoading`
+#[derive(Debug)]
+pub enum BodyError {
+    /// The body could not be received
+    HyperError(::hyper::Error),
+    /// The body, once decoded, is larger than the maximum body size
+    TooLarge,
+    /// The body is encoded with an unsupported content coding
+    UnsupportedEncoding(String),
+    /// The body could not be decoded with its content coding
+    InvalidEncoding(String),
+}
+
+impl BodyError {
+    /// The status of the response answering a request whose body could not be loaded
+    pub fn status(&self) -> crate::http::StatusCode {
+        use crate::http::StatusCode;
+        match self {
+            BodyError::HyperError(_) => StatusCode::BAD_REQUEST,
+            BodyError::TooLarge => StatusCode::PAYLOAD_TOO_LARGE,
+            BodyError::UnsupportedEncoding(_) => StatusCode::UNSUPPORTED_MEDIA_TYPE,
+            BodyError::InvalidEncoding(_) => StatusCode::BAD_REQUEST,
+        }
+    }
+}
+
+impl From<::hyper::Error> for BodyError {
+    fn from(e: ::hyper::Error) -> Self {
+        BodyError::HyperError(e)
+    }
+}
+
+impl ::std::error::Error for BodyError {
+    fn description(&self) -> &str {
+        use crate::error::BodyError::*;
+        match self {
+            HyperError(ref e) => e.description(),
+            TooLarge => "Request body too large",
+            UnsupportedEncoding(_) => "Unsupported request body encoding",
+            InvalidEncoding(_) => "Invalid request body encoding",
+        }
+    }
+}
+
+impl ::std::fmt::Display for BodyError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result<> {
+        use crate::error::BodyError::*;
+        match self {
+            HyperError(ref e) => e.fmt(f),
+            TooLarge => write!(f, "The request body is larger than the maximum body size"),
+            UnsupportedEncoding(ref encoding) => write!(f, "The request body encoding `{}` is not supported", encoding),
+            InvalidEncoding(ref encoding) => write!(f, "The request body could not be decoded as `{}`", encoding),
+        }
+    }
+}
@@ -11,6 +11,7 @@ use crate::http_types::response::Builder as ResponseBuilder;
 use crate::http_types::request::Parts as ReqParts;
 pub use crate::http_types::Extensions;
 use hashbrown::HashMap;
+use percent_encoding::percent_decode;
 
 /// Headers types re-export
 pub mod header {
@@ -24,10 +25,56 @@ use futures::Stream;
 use crate::http_types::HttpTryFrom;
 use std::any::Any;
 use std::collections::VecDeque;
+use std::sync::Arc;
 use crate::utils::UriPathMatcher;
+use crate::error::{BodyError, CookieError, MissingStateError, UrlError};
+use crate::cookies::{Cookie, CookieJar, CookieKeys};
+use crate::router::{UrlBuilder, TrailingSlash};
 
 static EMPTY_BODY: &[u8] = b"";
 
+/// How requests with a path segment that cannot be percent-decoded safely are handled, configured with
+/// `server::Builder::path_decoding`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathDecoding {
+    /// Requests with an encoded `/` (`%2F`) or a segment not decoding to valid UTF-8 are answered with `400 Bad Request`
+    Reject,
+    /// Such segments are kept percent-encoded, e.g. `a%2Fb` is matched and captured as is
+    KeepEncoded,
+}
+
+impl Default for PathDecoding {
+    fn default() -> Self {
+        PathDecoding::Reject
+    }
+}
+
+/// How requests with a non canonical path, e.g. `/a//b` or `/a/../b`, are handled, configured with
+/// `server::Builder::path_normalization`. A path going above the root, e.g. `/../a`, is always answered with `400 Bad Request`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PathNormalization {
+    /// Empty and `.` segments are removed and `..` segments remove the segment preceding them, before both the middleware
+    /// matching and the routing. The request uri is rewritten with the canonical path.
+    Normalize,
+    /// Requests with a non canonical path are answered with `400 Bad Request`
+    Reject,
+}
+
+impl Default for PathNormalization {
+    fn default() -> Self {
+        PathNormalization::Normalize
+    }
+}
+
+/// Percent-decode a path segment, an encoded `/` or invalid UTF-8 making the segment undecodable
+fn decode_path_segment(raw: &str) -> Option<String> {
+    if percent_decode(raw.as_bytes()).any(|b| b == b'/') {
+        return None;
+    }
+
+    percent_decode(raw.as_bytes()).decode_utf8().ok().map(|s| s.into_owned())
+}
+
 /// A Structure which represent an http request with a fully loaded body
 #[derive(Debug)]
 pub struct SyncRequest {
@@ -35,9 +82,30 @@ pub struct SyncRequest {
     head: ReqParts,
     /// Body
     body: Vec<u8>,
-    /// Request Params
+    /// Request Params, percent-decoded
     current_path: VecDeque<String>,
     captures: HashMap<String, String>,
+    /// Request Params, as received
+    current_raw_path: VecDeque<String>,
+    raw_captures: HashMap<String, String>,
+    /// Whether a path segment contains an encoded `/` or does not decode to valid UTF-8
+    undecodable_path: bool,
+    /// Whether the request path ends with a slash
+    trailing_slash: bool,
+    /// How routes handle the trailing slash of the request path
+    trailing_slash_policy: TrailingSlash,
+    /// Whether static path segments are matched regardless of case
+    case_insensitive_paths: bool,
+    /// Typed state shared between middlewares and handlers
+    state: Extensions,
+    /// Application-wide state registered on the server
+    app_state: AppState,
+    /// Url builder of the router dispatching the request
+    url_builder: Option<UrlBuilder>,
+    /// The paths and methods of the routes of the router handling the request
+    route_methods: Arc<Vec<(UriPathMatcher, Method)>>,
+    /// Cookies sent with the request
+    cookies: CookieJar,
 }
 
 impl SyncRequest {
@@ -46,16 +114,36 @@ impl SyncRequest {
     pub fn new(head: ReqParts,
                body: Vec<u8>,
     ) -> SyncRequest {
-        let mut cp = head.uri.path().to_owned().split('/').map(|s| s.to_owned()).collect::<VecDeque<String>>();
-        cp.pop_front();
-        if cp.back().map(|s| s.len()).unwrap_or(0) < 1 {
-            cp.pop_back();
+        let mut raw_cp = head.uri.path().to_owned().split('/').map(|s| s.to_owned()).collect::<VecDeque<String>>();
+        raw_cp.pop_front();
+        if raw_cp.back().map(|s| s.len()).unwrap_or(0) < 1 {
+            raw_cp.pop_back();
         }
+
+        let mut undecodable_path = false;
+        let cp = raw_cp.iter().map(|raw| decode_path_segment(raw).unwrap_or_else(|| {
+            undecodable_path = true;
+            raw.clone()
+        })).collect::<VecDeque<String>>();
+
+        let trailing_slash = head.uri.path().len() > 1 && head.uri.path().ends_with('/');
+        let cookies = CookieJar::from_headers(&head.headers);
         SyncRequest {
             head,
             body,
             current_path: cp,
             captures: HashMap::new(),
+            current_raw_path: raw_cp,
+            raw_captures: HashMap::new(),
+            undecodable_path,
+            trailing_slash,
+            trailing_slash_policy: TrailingSlash::default(),
+            case_insensitive_paths: false,
+            state: Extensions::new(),
+            app_state: AppState::default(),
+            url_builder: None,
+            route_methods: Arc::new(Vec::new()),
+            cookies,
         }
     }
 
@@ -123,69 +211,190 @@ impl SyncRequest {
 
     ///
     pub(crate) fn current_path_match(&mut self, path: &UriPathMatcher) -> bool {
-        let mut current_path = self.current_path.iter();
-        // validate path
-        for seg in path.iter() {
-            if let Some(current) = current_path.next() {
-                if !seg.matches(current) {
-                    return false;
-                }
-            } else {
-                return false;
-            }
+        if let Some(matched) = path.match_segments(&self.current_path, self.case_insensitive_paths) {
+            self.current_path_consume(path, matched);
+            return true;
         }
 
-        // Alter current path and capture path variable
-        {
-            for seg in path.iter() {
-                if let Some(current) = self.current_path.pop_front() {
-                    if let Some(name) = seg.name() {
-                        self.captures.insert(name.to_string(), current);
-                    }
-                }
-            }
+        false
+    }
+
+    ///
+    pub(crate) fn current_path_match_all(&mut self, path: &UriPathMatcher) -> bool {
+        if !self.current_path_matches_all(path) {
+            return false;
         }
 
+        let matched = self.current_path.len();
+        self.current_path_consume(path, matched);
+
         true
     }
 
-    ///
-    pub(crate) fn current_path_match_all(&mut self, path: &UriPathMatcher) -> bool {
-        if path.len() != self.current_path.len() {
+    /// Whether `path` matches the whole current path, without consuming it
+    pub(crate) fn current_path_matches_all(&self, path: &UriPathMatcher) -> bool {
+        if self.trailing_slash_policy != TrailingSlash::Ignore && path.trailing_slash() != self.trailing_slash {
             return false;
         }
 
-        let mut current_path = self.current_path.iter();
-        // validate path
+        path.match_segments(&self.current_path, self.case_insensitive_paths) == Some(self.current_path.len())
+    }
+
+    /// Alter current path and capture path variable
+    fn current_path_consume(&mut self, path: &UriPathMatcher, matched: usize) {
+        let mut remaining = matched;
+
         for seg in path.iter() {
-            if let Some(current) = current_path.next() {
-                if !seg.matches(current) {
-                    return false;
+            if seg.is_tail() {
+                let rest: Vec<String> = self.current_path.drain(..).collect();
+                let raw_rest: Vec<String> = self.current_raw_path.drain(..).collect();
+                if let Some(name) = seg.name() {
+                    self.captures.insert(name.to_string(), rest.join("/"));
+                    self.raw_captures.insert(name.to_string(), raw_rest.join("/"));
                 }
-            } else {
-                return false;
+                return;
             }
+
+            if remaining == 0 {
+                return;
+            }
+            remaining -= 1;
+
+            if let (Some(current), Some(raw)) = (self.current_path.pop_front(), self.current_raw_path.pop_front()) {
+                if let Some(name) = seg.name() {
+                    self.captures.insert(name.to_string(), current);
+                    self.raw_captures.insert(name.to_string(), raw);
+                }
+            }
+        }
+    }
+
+    /// Canonicalize the path: empty and `.` segments are removed, and `..` segments remove the segment preceding them.
+    /// The uri is rewritten when the path changes. Returns whether the path was already canonical, or `None` when a `..`
+    /// segment goes above the root.
+    pub(crate) fn normalize_path(&mut self) -> Option<bool> {
+        let is_dot = |s: &str| s == "." || s == "..";
+
+        if !self.current_path.iter().any(|s| s.is_empty() || is_dot(s)) {
+            return Some(true);
         }
 
-        // Alter current path and capture path variable
-        {
-            for seg in path.iter() {
-                if let Some(current) = self.current_path.pop_front() {
-                    if let Some(name) = seg.name() {
-                        self.captures.insert(name.to_string(), current);
-                    }
+        let ends_with_dot = self.current_path.back().map(|s| is_dot(s)).unwrap_or(false);
+        let mut path = VecDeque::with_capacity(self.current_path.len());
+        let mut raw_path = VecDeque::with_capacity(self.current_path.len());
+
+        for (segment, raw) in self.current_path.drain(..).zip(self.current_raw_path.drain(..)) {
+            match segment.as_str() {
+                "" | "." => {}
+                ".." => {
+                    path.pop_back()?;
+                    raw_path.pop_back();
+                }
+                _ => {
+                    path.push_back(segment);
+                    raw_path.push_back(raw);
                 }
             }
         }
 
-        true
+        // A path ending with a dot segment designates a directory, e.g. `/a/b/..` is `/a/`
+        self.trailing_slash = (self.trailing_slash || ends_with_dot) && !path.is_empty();
+
+        let mut canonical = String::new();
+        for raw in &raw_path {
+            canonical.push('/');
+            canonical.push_str(raw);
+        }
+        if canonical.is_empty() || self.trailing_slash {
+            canonical.push('/');
+        }
+        if let Some(query) = self.head.uri.query() {
+            canonical.push('?');
+            canonical.push_str(query);
+        }
+
+        let mut parts = self.head.uri.clone().into_parts();
+        parts.path_and_query = canonical.parse().ok();
+        if let Ok(uri) = Uri::from_parts(parts) {
+            self.head.uri = uri;
+        }
+
+        self.current_path = path;
+        self.current_raw_path = raw_path;
+
+        Some(false)
     }
 
-    ///
+    pub(crate) fn current_path(&self) -> &VecDeque<String> {
+        &self.current_path
+    }
+
+    /// Returns whether the request path ends with a slash, e.g. `/users/`
+    pub fn has_trailing_slash(&self) -> bool {
+        self.trailing_slash
+    }
+
+    pub(crate) fn trailing_slash_policy(&self) -> TrailingSlash {
+        self.trailing_slash_policy
+    }
+
+    pub(crate) fn set_trailing_slash_policy(&mut self, policy: TrailingSlash) {
+        self.trailing_slash_policy = policy;
+    }
+
+    pub(crate) fn case_insensitive_paths(&self) -> bool {
+        self.case_insensitive_paths
+    }
+
+    /// Static path segments are matched regardless of case once set, by the router and by the middleware rules
+    pub(crate) fn set_case_insensitive_paths(&mut self) {
+        self.case_insensitive_paths = true;
+    }
+
+    /// Returns the values captured by the named segments of the matched route, percent-decoded
     pub fn captures(&self) -> &HashMap<String, String> {
         &self.captures
     }
 
+    /// Returns the values captured by the named segments of the matched route, as they were received
+    pub fn raw_captures(&self) -> &HashMap<String, String> {
+        &self.raw_captures
+    }
+
+    /// Returns the host the request is addressed to, from the uri authority or the `Host` header, lowercased and
+    /// without its port
+    pub fn host(&self) -> Option<String> {
+        let authority = match self.head.uri.authority_part() {
+            Some(authority) => authority.as_str().to_string(),
+            None => self.head.headers.get(header::HOST)?.to_str().ok()?.to_string(),
+        };
+
+        let authority = authority.rsplit('@').next().unwrap_or("");
+        let host = if authority.starts_with('[') {
+            authority.split(']').next().map(|h| format!("{}]", h)).unwrap_or_default()
+        } else {
+            authority.split(':').next().unwrap_or("").to_string()
+        };
+
+        if host.is_empty() {
+            None
+        } else {
+            Some(host.to_lowercase())
+        }
+    }
+
+    /// Add a value captured outside of the path, e.g. by a host pattern
+    pub(crate) fn add_capture(&mut self, name: String, value: String) {
+        self.raw_captures.insert(name.clone(), value.clone());
+        self.captures.insert(name, value);
+    }
+
+    /// Returns whether a segment of the request path contains an encoded `/` or does not decode to valid UTF-8.
+    /// Such segments are matched and captured without being decoded.
+    pub fn has_undecodable_path(&self) -> bool {
+        self.undecodable_path
+    }
+
     /// Returns the associated version.
     ///
     /// # Examples
@@ -297,6 +506,163 @@ impl SyncRequest {
         &mut self.head.extensions
     }
 
+    /// Insert a typed state entry into the request, replacing and returning any previous entry of the same type.
+    /// This is the way for a middleware to pass data (e.g. an authenticated user) to the handlers.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use saphir::*;
+    /// struct AuthenticatedUser(String);
+    ///
+    /// struct AuthMiddleware;
+    ///
+    /// impl Middleware for AuthMiddleware {
+    ///     fn resolve(&self, req: &mut SyncRequest, _res: &mut SyncResponse) -> RequestContinuation {
+    ///         req.insert_state(AuthenticatedUser("john".to_string()));
+    ///         RequestContinuation::Continue
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn insert_state<T: Send + Sync + 'static>(&mut self, value: T) -> Option<T> {
+        self.state.insert(value)
+    }
+
+    /// Returns a reference to the state entry of type `T`, if any.
+    #[inline]
+    pub fn state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.state.get::<T>()
+    }
+
+    /// Returns a mutable reference to the state entry of type `T`, if any.
+    #[inline]
+    pub fn state_mut<T: Send + Sync + 'static>(&mut self) -> Option<&mut T> {
+        self.state.get_mut::<T>()
+    }
+
+    /// Remove and return the state entry of type `T`, if any.
+    #[inline]
+    pub fn remove_state<T: Send + Sync + 'static>(&mut self) -> Option<T> {
+        self.state.remove::<T>()
+    }
+
+    /// Returns a reference to the state entry of type `T`, or an error naming the missing type.
+    /// Use this in handlers relying on a middleware to provide the state.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use saphir::*;
+    /// struct AuthenticatedUser(String);
+    ///
+    /// fn handler(_ctx: &(), req: &SyncRequest, res: &mut SyncResponse) {
+    ///     match req.require_state::<AuthenticatedUser>() {
+    ///         Ok(user) => { res.status(StatusCode::OK).body(user.0.clone()); },
+    ///         Err(e) => { res.status(StatusCode::INTERNAL_SERVER_ERROR).body(e.to_string()); },
+    ///     }
+    /// }
+    /// ```
+    pub fn require_state<T: Send + Sync + 'static>(&self) -> Result<&T, MissingStateError> {
+        self.state.get::<T>().ok_or_else(MissingStateError::of::<T>)
+    }
+
+    ///
+    pub(crate) fn set_app_state(&mut self, app_state: AppState) {
+        self.app_state = app_state;
+    }
+
+    /// Returns a reference to the application-wide state entry of type `T`, as registered with `server::Builder::with_state`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use saphir::*;
+    /// struct Config { greeting: String }
+    ///
+    /// fn handler(_ctx: &(), req: &SyncRequest, res: &mut SyncResponse) {
+    ///     if let Some(config) = req.app_state::<Config>() {
+    ///         res.status(StatusCode::OK).body(config.greeting.clone());
+    ///     }
+    /// }
+    /// ```
+    #[inline]
+    pub fn app_state<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.app_state.get::<T>()
+    }
+
+    /// Returns a reference to the application-wide state entry of type `T`, or an error naming the missing type.
+    pub fn require_app_state<T: Send + Sync + 'static>(&self) -> Result<&T, MissingStateError> {
+        self.app_state.get::<T>().ok_or_else(MissingStateError::of_app::<T>)
+    }
+
+    /// The outermost router dispatching the request provides the url builder
+    pub(crate) fn set_url_builder(&mut self, url_builder: &UrlBuilder) {
+        if self.url_builder.is_none() {
+            self.url_builder = Some(url_builder.clone());
+        }
+    }
+
+    pub(crate) fn set_route_methods(&mut self, route_methods: &Arc<Vec<(UriPathMatcher, Method)>>) {
+        self.route_methods = route_methods.clone();
+    }
+
+    /// Returns the methods of the routes matching the remaining request path, e.g. to answer an `OPTIONS` request.
+    /// Only routes listed by `Router::routes` are considered.
+    pub fn route_methods(&self) -> Vec<Method> {
+        let mut methods: Vec<Method> = Vec::new();
+        for &(ref path_m, ref method) in self.route_methods.iter() {
+            let matched = path_m.match_segments(&self.current_path, self.case_insensitive_paths || path_m.case_insensitive());
+            if matched == Some(self.current_path.len()) && !methods.contains(method) {
+                methods.push(method.clone());
+            }
+        }
+
+        methods
+    }
+
+    /// The router of a host provides the url builder of the requests it handles, see `router::Builder::host`
+    pub(crate) fn replace_url_builder(&mut self, url_builder: &UrlBuilder) {
+        self.url_builder = Some(url_builder.clone());
+    }
+
+    /// Generate the url of the route named `name` in the router dispatching the request, see `Router::url_for`.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use saphir::*;
+    /// fn create(_ctx: &(), req: &SyncRequest, res: &mut SyncResponse) {
+    ///     if let Ok(location) = req.url_for("read_user", &[("id", "42")]) {
+    ///         res.status(StatusCode::CREATED).header(header::LOCATION, location.as_str());
+    ///     }
+    /// }
+    /// ```
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.url_builder.as_ref().ok_or(UrlError::NoRouter)?.url_for(name, params)
+    }
+
+    /// Returns the cookies sent with the request.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use saphir::*;
+    /// fn handler(_ctx: &(), req: &SyncRequest, res: &mut SyncResponse) {
+    ///     let visits = req.cookies().value::<u32>("visits").unwrap_or(0);
+    ///     let user = req.cookies().get_private("user").map(|c| c.value().to_string());
+    ///     res.cookie(Cookie::new("visits", (visits + 1).to_string()));
+    /// }
+    /// ```
+    #[inline]
+    pub fn cookies(&self) -> &CookieJar {
+        &self.cookies
+    }
+
+    pub(crate) fn set_cookie_keys(&mut self, keys: Option<Arc<CookieKeys>>) {
+        self.cookies.set_keys(keys);
+    }
+
     /// Returns a reference to the associated HTTP body.
     ///
     /// # Examples
@@ -329,6 +695,97 @@ impl SyncRequest {
     }
 }
 
+/// Application-wide typed state, registered once on the server builder and shared by every request.
+/// Cloning is cheap, every clone refers to the same entries.
+#[derive(Debug, Clone, Default)]
+pub struct AppState {
+    inner: Arc<Extensions>,
+}
+
+impl AppState {
+    ///
+    pub(crate) fn new(entries: Extensions) -> Self {
+        AppState {
+            inner: Arc::new(entries),
+        }
+    }
+
+    /// Returns a reference to the state entry of type `T`, if any.
+    #[inline]
+    pub fn get<T: Send + Sync + 'static>(&self) -> Option<&T> {
+        self.inner.get::<T>()
+    }
+}
+
+/// The maximum size of a decoded body when no maximum body size is set, in bytes
+#[cfg(feature = "compression")]
+pub const DEFAULT_MAX_DECODED_SIZE: u64 = 16 * 1024 * 1024;
+
+/// How the body of a request is loaded
+#[derive(Debug, Clone, Default)]
+pub struct BodyLoading {
+    max_size: Option<u64>,
+    #[cfg(feature = "compression")]
+    decompress: bool,
+}
+
+impl BodyLoading {
+    /// Load bodies of any size as they were received
+    pub fn new() -> Self {
+        BodyLoading::default()
+    }
+
+    /// Reject bodies larger than `max_size` bytes, once decoded
+    pub fn max_size(mut self, max_size: u64) -> Self {
+        self.max_size = Some(max_size);
+        self
+    }
+
+    /// Decode the bodies encoded with the gzip, deflate or br content codings, rejecting other codings. Decoded bodies
+    /// larger than `DEFAULT_MAX_DECODED_SIZE` are rejected when no maximum size is set.
+    #[cfg(feature = "compression")]
+    pub fn decompress(mut self) -> Self {
+        self.decompress = true;
+        self
+    }
+
+    /// Receive the body of a request, rejecting it once it exceeds the maximum size. The body is not decoded yet.
+    pub(crate) fn load(&self, req: Request<Body>) -> Box<Future<Item=SyncRequest, Error=BodyError> + Send> {
+        let (parts, body) = req.into_parts();
+        let max_size = self.max_size;
+
+        // The announced length is checked before receiving the body
+        let announced = parts.headers.get(header::CONTENT_LENGTH).and_then(|l| l.to_str().ok()).and_then(|l| l.parse::<u64>().ok());
+        if let (Some(max_size), Some(announced)) = (max_size, announced) {
+            if announced > max_size {
+                return Box::new(::futures::future::err(BodyError::TooLarge));
+            }
+        }
+
+        Box::new(body.map_err(BodyError::from).fold(Vec::new(), move |mut body_vec, chunk| {
+            if max_size.map_or(false, |max_size| (body_vec.len() + chunk.len()) as u64 > max_size) {
+                return Err(BodyError::TooLarge);
+            }
+
+            body_vec.extend_from_slice(&chunk);
+            Ok(body_vec)
+        }).map(move |body_vec| SyncRequest::new(parts, body_vec)))
+    }
+
+    /// Decode the body of a loaded request, this is meant to run on a worker thread
+    #[cfg(feature = "compression")]
+    pub(crate) fn decode(&self, req: &mut SyncRequest) -> Result<(), BodyError> {
+        if !self.decompress {
+            return Ok(());
+        }
+
+        let body = ::std::mem::replace(req.body_mut(), Vec::new());
+        let max_size = self.max_size.unwrap_or(DEFAULT_MAX_DECODED_SIZE);
+        *req.body_mut() = crate::compression::decode_request_body(req.headers_map_mut(), body, max_size)?;
+        Ok(())
+    }
+}
+
 /// A trait allowing the implicit conversion of a Hyper::Request into a SyncRequest
 pub trait LoadBody {
     ///
@@ -349,6 +806,7 @@ impl LoadBody for Request<Body> {
 pub struct SyncResponse {
     builder: ResponseBuilder,
     body: Box<ToBody>,
+    cookie_keys: Option<Arc<CookieKeys>>,
 }
 
 impl SyncResponse {
@@ -357,9 +815,14 @@ impl SyncResponse {
         SyncResponse {
             builder: ResponseBuilder::new(),
             body: Box::new(EMPTY_BODY),
+            cookie_keys: None,
         }
     }
 
+    pub(crate) fn set_cookie_keys(&mut self, keys: Option<Arc<CookieKeys>>) {
+        self.cookie_keys = keys;
+    }
+
     /// Set the HTTP status for this response.
     ///
     /// This function will configure the HTTP status code of the `Response` that
@@ -447,6 +910,53 @@ impl SyncResponse {
         self
     }
 
+    /// Set a cookie on the client with a `Set-Cookie` header.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use saphir::*;
+    /// let mut response = SyncResponse::new();
+    /// response.cookie(Cookie::build("theme", "dark")
+    ///     .path("/")
+    ///     .http_only(true)
+    ///     .secure(true)
+    ///     .same_site(SameSite::Lax)
+    ///     .max_age(cookies::Duration::days(30))
+    ///     .finish());
+    /// ```
+    pub fn cookie(&mut self, cookie: Cookie<'static>) -> &mut SyncResponse {
+        self.builder.header(header::SET_COOKIE, cookie.to_string().as_str());
+        self
+    }
+
+    /// Set a cookie signed with the current cookie key, so its value can be read but not tampered with by the client,
+    /// see `CookieJar::get_signed`
+    pub fn signed_cookie(&mut self, cookie: Cookie<'static>) -> Result<&mut SyncResponse, CookieError> {
+        let cookie = self.cookie_keys.as_ref().ok_or(CookieError::NoKeys)?.sign(cookie);
+        Ok(self.cookie(cookie))
+    }
+
+    /// Set a cookie encrypted and signed with the current cookie key, so its value can be neither read nor tampered
+    /// with by the client, see `CookieJar::get_private`
+    pub fn private_cookie(&mut self, cookie: Cookie<'static>) -> Result<&mut SyncResponse, CookieError> {
+        let cookie = self.cookie_keys.as_ref().ok_or(CookieError::NoKeys)?.encrypt(cookie);
+        Ok(self.cookie(cookie))
+    }
+
+    /// Remove a cookie from the client, the cookie must have the same path and domain as when it was set.
+    ///
+    /// # Examples
+    ///
+    /// ```rust,no_run
+    /// # use saphir::*;
+    /// let mut response = SyncResponse::new();
+    /// response.remove_cookie(Cookie::build("theme", "").path("/").finish());
+    /// ```
+    pub fn remove_cookie(&mut self, cookie: Cookie<'static>) -> &mut SyncResponse {
+        self.cookie(crate::cookies::removal(cookie))
+    }
+
     /// Adds an extension to this builder
     ///
     /// # Examples
@@ -490,7 +1000,7 @@ impl SyncResponse {
 
     ///
     pub fn build_response(self) -> Result<Response<Body>, crate::http_types::Error> {
-        let SyncResponse { mut builder, body } = self;
+        let SyncResponse { mut builder, body, .. } = self;
         let b: Body = body.to_body();
         builder.body(b)
     }
@@ -24,6 +24,20 @@ pub mod controller;
 pub mod router;
 /// Modules for the http server
 pub mod server;
+/// Modules for the static files serving
+pub mod static_files;
+/// Modules for the cross-origin requests handling
+pub mod cors;
+/// Modules for the cookies handling
+pub mod cookies;
+/// Modules for the server-side sessions
+pub mod session;
+/// Modules for the response compression
+#[cfg(feature = "compression")]
+pub mod compression;
+/// Modules for the OpenAPI document generation
+#[cfg(feature = "openapi")]
+pub mod openapi;
 
 use ::http as http_types;
 pub use regex;
@@ -34,12 +48,33 @@ pub use crate::http::*;
 pub use crate::utils::RequestContinuation;
 pub use crate::middleware::Middleware;
 pub use crate::middleware::MiddlewareStack;
+pub use crate::cors::Cors;
+pub use crate::cookies::{Cookie, CookieJar, CookieKeys, SameSite};
+pub use crate::session::{Session, Sessions, SessionStore, MemoryStore};
 pub use crate::controller::Controller;
 pub use crate::controller::BasicController;
-pub use crate::controller::ControllerDispatch;
 pub use crate::controller::RequestGuard;
 pub use crate::controller::RequestGuardCollection;
 pub use crate::controller::BodyGuard;
+pub use crate::controller::RouteInfo;
+pub use crate::controller::RouteSegment;
+pub use crate::controller::RouteDoc;
+pub use crate::controller::StateGuard;
+pub use crate::static_files::StaticFilesController;
+pub use crate::static_files::EmbeddedAssetsController;
+#[cfg(feature = "compression")]
+pub use crate::compression::Compression;
 pub use crate::router::Router;
+pub use crate::router::UrlBuilder;
+pub use crate::router::TrailingSlash;
+pub use crate::router::VersionSelector;
 pub use crate::server::{Server, ServerSpawn};
-pub use crate::error::ServerError;
\ No newline at end of file
+pub use crate::error::ServerError;
+pub use crate::error::MissingStateError;
+pub use crate::error::RouteError;
+pub use crate::error::UrlError;
+pub use crate::error::BodyError;
+pub use crate::error::CookieError;
+pub use crate::error::SessionError;
+#[cfg(feature = "macro")]
+pub use saphir_macro::*;
\ No newline at end of file
@@ -1,3 +1,4 @@
+use std::collections::VecDeque;
 use std::sync::Arc;
 
 use log::error;
@@ -57,7 +58,7 @@ impl MiddlewareStack {
     ///
     pub fn resolve(&self, req: &mut SyncRequest, res: &mut SyncResponse) -> RequestContinuation {
         for &(ref rule, ref middleware) in self.middlewares.iter() {
-            if rule.validate_path(req.uri().path()) {
+            if rule.validate_path(req.current_path(), req.case_insensitive_paths()) {
                 if let Stop = middleware.resolve(req, res) {
                     return Stop;
                 }
@@ -66,6 +67,30 @@ impl MiddlewareStack {
 
         Continue
     }
+
+    /// Resolve the middlewares, call `handler` if they all let the request continue, then call `Middleware::after` on
+    /// every resolved middleware, in the reverse order
+    pub fn handle<F>(&self, req: &mut SyncRequest, res: &mut SyncResponse, handler: F) where F: FnOnce(&mut SyncRequest, &mut SyncResponse) {
+        let mut resolved = Vec::new();
+        let mut continuation = Continue;
+        for &(ref rule, ref middleware) in self.middlewares.iter() {
+            if rule.validate_path(req.current_path(), req.case_insensitive_paths()) {
+                resolved.push(middleware);
+                continuation = middleware.resolve(req, res);
+                if let Stop = continuation {
+                    break;
+                }
+            }
+        }
+
+        if let Continue = continuation {
+            handler(req, res);
+        }
+
+        for middleware in resolved.into_iter().rev() {
+            middleware.after(req, res);
+        }
+    }
 }
 
 impl Clone for MiddlewareStack {
@@ -82,6 +107,10 @@ pub trait Middleware: Send + Sync {
     /// and doesn't match any exclusion. Returning `RequestContinuation::Continue` will allow the request to continue through the stack, and
     /// returning `RequestContinuation::Stop` will cease the request processing, returning as response the modified `res` param.
     fn resolve(&self, req: &mut SyncRequest, res: &mut SyncResponse) -> RequestContinuation;
+
+    /// This method will be invoked once the request was handled, if `resolve` was invoked, even if it stopped the request
+    /// processing. Middlewares are invoked in the reverse order they were resolved, so they can amend the response.
+    fn after(&self, _req: &mut SyncRequest, _res: &mut SyncResponse) {}
 }
 
 struct MiddlewareRule {
@@ -97,10 +126,10 @@ impl MiddlewareRule {
         }
     }
 
-    pub fn validate_path(&self, path: &str) -> bool {
-        if self.included_path.iter().any(|m_p| m_p.match_start(path)) {
+    pub fn validate_path(&self, path: &VecDeque<String>, case_insensitive: bool) -> bool {
+        if self.included_path.iter().any(|m_p| m_p.match_segments(path, case_insensitive).is_some()) {
             if let Some(ref excluded_path) = self.excluded_path {
-                return !excluded_path.iter().any(|m_e_p| m_e_p.match_start(path));
+                return !excluded_path.iter().any(|m_e_p| m_e_p.match_segments(path, case_insensitive).is_some());
             } else {
                 return true;
             }
@@ -0,0 +1,210 @@
+use log::warn;
+use serde_json::{json, Map, Value};
+
+use crate::controller::{RouteInfo, RouteSegment};
+
+const OPENAPI_VERSION: &str = "3.0.2";
+
+/// General information of a generated OpenAPI document
+#[derive(Debug, Clone)]
+pub struct OpenApiInfo {
+    title: String,
+    version: String,
+    description: Option<String>,
+    servers: Vec<String>,
+}
+
+impl OpenApiInfo {
+    /// Create the document information with the api `title` and `version`
+    pub fn new(title: &str, version: &str) -> Self {
+        OpenApiInfo {
+            title: title.to_string(),
+            version: version.to_string(),
+            description: None,
+            servers: Vec::new(),
+        }
+    }
+
+    /// Set the api description
+    pub fn description(mut self, description: &str) -> Self {
+        self.description = Some(description.to_string());
+        self
+    }
+
+    /// Add a server url to the document
+    pub fn server(mut self, url: &str) -> Self {
+        self.servers.push(url.to_string());
+        self
+    }
+}
+
+/// Generate an OpenAPI 3 document describing the provided routes.
+///
+/// Each variable path segment becomes a required path parameter, its regex being used as the parameter pattern.
+/// Unnamed variable segments are named after their position in the path, and a route ending with optional segments is
+/// documented once for each of its possible lengths. The documentation attached with
+/// `controller::Builder::with_doc` is used for the summary, description, tags, request body and responses of the operations.
+pub fn generate(info: &OpenApiInfo, routes: &[RouteInfo]) -> Value {
+    let mut paths = Map::new();
+
+    for route in routes {
+        let required = route.segments.iter().take_while(|s| match s {
+            RouteSegment::Variable { optional, .. } => !optional,
+            _ => true,
+        }).count();
+
+        for len in required..=route.segments.len() {
+            let (template, parameters) = path_template(&route.segments[..len], route.trailing_slash);
+
+            let path_item = paths.entry(template.clone()).or_insert_with(|| Value::Object(Map::new()));
+            let method = route.method.as_str().to_lowercase();
+
+            if let Some(operations) = path_item.as_object_mut() {
+                if operations.contains_key(&method) {
+                    warn!("OpenAPI: {} {} is described by more than one route, only the first one is documented", route.method, template);
+                    continue;
+                }
+
+                let mut op = operation(route, parameters);
+                if len < route.segments.len() {
+                    // Operation ids are unique, only the full path of the route gets one
+                    if let Some(op) = op.as_object_mut() {
+                        op.remove("operationId");
+                    }
+                }
+
+                operations.insert(method, op);
+            }
+        }
+    }
+
+    let mut info_obj = Map::new();
+    info_obj.insert("title".to_string(), Value::String(info.title.clone()));
+    info_obj.insert("version".to_string(), Value::String(info.version.clone()));
+    if let Some(ref description) = info.description {
+        info_obj.insert("description".to_string(), Value::String(description.clone()));
+    }
+
+    let mut document = Map::new();
+    document.insert("openapi".to_string(), Value::String(OPENAPI_VERSION.to_string()));
+    document.insert("info".to_string(), Value::Object(info_obj));
+    if !info.servers.is_empty() {
+        document.insert("servers".to_string(), Value::Array(info.servers.iter().map(|url| json!({ "url": url })).collect()));
+    }
+    document.insert("paths".to_string(), Value::Object(paths));
+
+    Value::Object(document)
+}
+
+fn path_template(segments: &[RouteSegment], trailing_slash: bool) -> (String, Vec<Value>) {
+    let mut template = String::new();
+    let mut parameters = Vec::new();
+
+    for (index, segment) in segments.iter().enumerate() {
+        template.push('/');
+
+        match segment {
+            RouteSegment::Static(ref s) => template.push_str(s),
+            RouteSegment::Variable { ref name, ref pattern, .. } => {
+                let name = name.clone().unwrap_or_else(|| format!("param{}", index));
+                template.push_str(&format!("{{{}}}", name));
+
+                let mut schema = json!({ "type": "string" });
+                if let Some(ref pattern) = pattern {
+                    schema["pattern"] = Value::String(pattern.clone());
+                }
+
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "schema": schema,
+                }));
+            }
+            RouteSegment::Tail { ref name } => {
+                let name = name.clone().unwrap_or_else(|| format!("param{}", index));
+                template.push_str(&format!("{{{}}}", name));
+
+                parameters.push(json!({
+                    "name": name,
+                    "in": "path",
+                    "required": true,
+                    "description": "The rest of the path, which can span many segments",
+                    "schema": { "type": "string" },
+                }));
+            }
+        }
+    }
+
+    if template.is_empty() || trailing_slash {
+        template.push('/');
+    }
+
+    (template, parameters)
+}
+
+fn operation(route: &RouteInfo, parameters: Vec<Value>) -> Value {
+    let mut op = Map::new();
+
+    if let Some(ref name) = route.name {
+        op.insert("operationId".to_string(), Value::String(name.clone()));
+    }
+
+    if !parameters.is_empty() {
+        op.insert("parameters".to_string(), Value::Array(parameters));
+    }
+
+    if !route.guards.is_empty() {
+        op.insert("x-guards".to_string(), json!(route.guards));
+    }
+
+    let mut responses = Map::new();
+
+    if let Some(ref doc) = route.doc {
+        if let Some(ref summary) = doc.summary {
+            op.insert("summary".to_string(), Value::String(summary.clone()));
+        }
+
+        if let Some(ref description) = doc.description {
+            op.insert("description".to_string(), Value::String(description.clone()));
+        }
+
+        if !doc.tags.is_empty() {
+            op.insert("tags".to_string(), json!(doc.tags));
+        }
+
+        if let Some((ref content_type, ref schema)) = doc.request_body {
+            op.insert("requestBody".to_string(), json!({
+                "required": true,
+                "content": content(content_type, schema),
+            }));
+        }
+
+        for &(status, ref description, ref body) in &doc.responses {
+            let mut response = json!({ "description": description });
+            if let Some((ref content_type, ref schema)) = body {
+                response["content"] = content(content_type, schema);
+            }
+            responses.insert(status.to_string(), response);
+        }
+    }
+
+    if responses.is_empty() {
+        responses.insert("default".to_string(), json!({ "description": "Default response" }));
+    }
+
+    op.insert("responses".to_string(), Value::Object(responses));
+
+    Value::Object(op)
+}
+
+fn content(content_type: &str, schema: &str) -> Value {
+    let schema = serde_json::from_str::<Value>(schema).unwrap_or_else(|e| {
+        warn!("OpenAPI: invalid schema for {}: {}", content_type, e);
+        json!({})
+    });
+
+    let mut content = Map::new();
+    content.insert(content_type.to_string(), json!({ "schema": schema }));
+    Value::Object(content)
+}
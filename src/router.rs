@@ -1,12 +1,166 @@
 use std::sync::Arc;
 
-use crate::controller::Controller;
+use hashbrown::HashMap;
+use percent_encoding::{utf8_percent_encode, PATH_SEGMENT_ENCODE_SET};
+use regex::Regex;
+
+use crate::controller::{Controller, BasicController, RouteInfo, RouteSegment, tail_precedence};
+use crate::error::{RouteError, UrlError};
 use crate::http::*;
-use crate::utils::UriPathMatcher;
+use crate::http::header::Header;
+use crate::middleware::MiddlewareStack;
+use crate::utils::{HostMatcher, UriPathMatcher, json_escape};
+#[cfg(feature = "openapi")]
+use crate::openapi::OpenApiInfo;
+
+/// How routes handle a request path ending with a slash, configured with `server::Builder::trailing_slash`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TrailingSlash {
+    /// `/users` and `/users/` only match routes declared respectively without and with a trailing slash
+    Strict,
+    /// The trailing slash is ignored, `/users` and `/users/` match the same routes
+    Ignore,
+    /// Routes are matched strictly, but a request only matching a route once its trailing slash is added or removed
+    /// is answered with a `308 Permanent Redirect` towards that route
+    Redirect,
+}
+
+impl Default for TrailingSlash {
+    fn default() -> Self {
+        TrailingSlash::Ignore
+    }
+}
+
+/// How the api version of a request is selected among the versions registered with `router::Builder::version`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VersionSelector {
+    /// The version is the first segment of the path, e.g. `/v2/users`
+    PathPrefix,
+    /// The version is the value of a header, e.g. `X-Api-Version: v2`. Naming an unknown version is answered with
+    /// `400 Bad Request`.
+    Header(String),
+    /// The version is read from the `Accept` media types of the given vendor, either from the vendor tree, e.g.
+    /// `application/vnd.<vendor>.v2+json`, or from a `version` parameter, e.g. `application/json; version=v2`.
+    /// The known version of highest quality is selected, accepting only unknown versions is answered with
+    /// `406 Not Acceptable`.
+    MediaType(String),
+}
+
+impl Default for VersionSelector {
+    fn default() -> Self {
+        VersionSelector::PathPrefix
+    }
+}
+
+/// The routers of the api versions registered on a router
+struct ApiVersions {
+    selector: VersionSelector,
+    default_version: Option<String>,
+    groups: Vec<VersionGroup>,
+}
+
+struct VersionGroup {
+    version: String,
+    prefix: UriPathMatcher,
+    router: Router,
+    deprecated: bool,
+}
+
+impl ApiVersions {
+    fn group(&self, version: &str) -> Option<&VersionGroup> {
+        self.groups.iter().find(|g| g.version == version)
+    }
+
+    /// Select the version group handling the request, `None` leaving the request to the unversioned routes
+    fn select(&self, req: &mut SyncRequest) -> Result<Option<&VersionGroup>, StatusCode> {
+        let requested = match self.selector {
+            VersionSelector::PathPrefix => {
+                if let Some(group) = self.groups.iter().find(|g| req.current_path_match(&g.prefix)) {
+                    return Ok(Some(group));
+                }
+                None
+            }
+            VersionSelector::Header(ref name) => match req.headers_map().get(name.as_str()).and_then(|v| v.to_str().ok()) {
+                Some(version) => Some(self.group(version.trim()).ok_or(StatusCode::BAD_REQUEST)?),
+                None => None,
+            },
+            VersionSelector::MediaType(ref vendor) => {
+                let vendor_prefix = format!("vnd.{}.", vendor);
+                let accept = header::Accept::parse_header(&req.headers_map().get_all(header::ACCEPT)).map(|a| a.0).unwrap_or_default();
+                let mut versions: Vec<(String, header::Quality)> = accept.iter().filter_map(|item| {
+                    let subtype = item.item.subtype();
+                    if subtype.as_str().starts_with(&vendor_prefix) {
+                        Some((subtype.as_str()[vendor_prefix.len()..].to_string(), item.quality))
+                    } else {
+                        item.item.get_param("version").map(|v| (v.as_str().to_string(), item.quality))
+                    }
+                }).collect();
+
+                if versions.is_empty() {
+                    None
+                } else {
+                    // The version preferred by the client is selected, the first listed one on equal quality
+                    versions.sort_by(|a, b| b.1.cmp(&a.1));
+                    let group = versions.iter()
+                        .filter(|&&(_, quality)| quality > header::q(0u16))
+                        .filter_map(|&(ref v, _)| self.group(v))
+                        .next();
+                    Some(group.ok_or(StatusCode::NOT_ACCEPTABLE)?)
+                }
+            }
+        };
+
+        let group = requested.or_else(|| self.default_version.as_ref().and_then(|v| self.group(v)));
+
+        // Requests matching no route of the version are left to the unversioned routes, e.g. a health check
+        Ok(group.filter(|g| g.router.matches(req)))
+    }
+}
+
+/// The routers of the hosts registered on a router
+struct VirtualHosts {
+    groups: Vec<HostGroup>,
+    default_host: Option<usize>,
+}
+
+struct HostGroup {
+    host: HostMatcher,
+    router: Router,
+}
+
+impl VirtualHosts {
+    /// Select the host group handling the request along with the labels captured from its host, `None` leaving the
+    /// request to the routes registered outside of any host
+    fn select(&self, req: &SyncRequest) -> Option<(&HostGroup, Vec<(String, String)>)> {
+        req.host()
+            .and_then(|host| self.groups.iter().filter_map(|g| g.host.match_host(&host).map(|c| (g, c))).next())
+            .or_else(|| self.default_host.map(|i| (&self.groups[i], Vec::new())))
+    }
+}
+
+/// A router mounted under a prefix with its own middleware stack
+struct Mount {
+    prefix: UriPathMatcher,
+    router: Router,
+    middleware_stack: MiddlewareStack,
+}
 
 ///
 pub struct Builder {
-    routes: Vec<(UriPathMatcher, Box<Controller>)>
+    routes: Vec<(UriPathMatcher, Box<Controller>)>,
+    mounts: Vec<Mount>,
+    hosts: Vec<(HostMatcher, Router)>,
+    default_host: Option<String>,
+    versions: Vec<(String, Router)>,
+    version_selector: VersionSelector,
+    default_version: Option<String>,
+    deprecated_versions: Vec<String>,
+    route_listing: Option<String>,
+    case_insensitive: bool,
+    #[cfg(feature = "openapi")]
+    openapi: Option<(String, OpenApiInfo)>,
+    /// The first configuration error, returned by `build`
+    error: Option<RouteError>,
 }
 
 ///
@@ -14,7 +168,25 @@ impl Builder {
     /// Create a new router builder
     pub fn new() -> Self {
         Builder {
-            routes: Vec::new()
+            routes: Vec::new(),
+            mounts: Vec::new(),
+            hosts: Vec::new(),
+            default_host: None,
+            versions: Vec::new(),
+            version_selector: VersionSelector::default(),
+            default_version: None,
+            deprecated_versions: Vec::new(),
+            route_listing: None,
+            case_insensitive: false,
+            #[cfg(feature = "openapi")]
+            openapi: None,
+            error: None,
+        }
+    }
+
+    fn fail(&mut self, error: RouteError) {
+        if self.error.is_none() {
+            self.error = Some(error);
         }
     }
 
@@ -22,16 +194,19 @@ impl Builder {
     /// # Example
     /// ```rust,no_run
     /// let u8_context = 1;
-    /// let u8_controller = BasicController::new(u8_context);
-    /// u8_controller.add(Method::Get, "^/test$", |ctx, req, res| { println!("this will handle Get request done on <your_host>/test")});
+    /// let u8_controller = BasicController::builder("/test", u8_context)
+    ///     .add(Method::GET, "/", |ctx, req, res| { println!("this will handle Get request done on <your_host>/test/")})
+    ///     .build()
+    ///     .expect("Invalid routes");
     ///
-    /// let mut router = Router::new();
-    /// router.add("/test", u8_controller);
+    /// let router = router::Builder::new().add(u8_controller).build().expect("Invalid routes");
     ///
     /// ```
     pub fn add<C: 'static + Controller>(mut self, controller: C) -> Self {
-        let path_m = UriPathMatcher::new(controller.base_path()).expect("Unable to construct path");
-        self.routes.push((path_m, Box::new(controller)));
+        match UriPathMatcher::new(controller.base_path()) {
+            Ok(path_m) => self.routes.push((path_m, Box::new(controller))),
+            Err(reason) => self.fail(RouteError::InvalidPath { path: controller.base_path().to_string(), reason }),
+        }
 
         self
     }
@@ -40,36 +215,314 @@ impl Builder {
     /// # Example
     /// ```rust,no_run
     /// let u8_context = 1;
-    /// let u8_controller = BasicController::new(u8_context);
-    /// u8_controller.add(Method::Get, "^/test$", |ctx, req, res| { println!("this will handle Get request done on <your_host>/test")});
+    /// let u8_controller = BasicController::builder("/test", u8_context)
+    ///     .add(Method::GET, "/", |ctx, req, res| { println!("this will handle Get request done on <your_host>/api/test/")})
+    ///     .build()
+    ///     .expect("Invalid routes");
     ///
-    /// let mut router = Router::new();
-    /// router.add("/test", u8_controller);
+    /// let router = router::Builder::new().route("/api", u8_controller).build().expect("Invalid routes");
     ///
     /// ```
     pub fn route<C: 'static + Controller>(mut self, route: &str, controller: C) -> Self {
-        let route_matcher = UriPathMatcher::new(route).and_then(|mut u| {u.append(controller.base_path())?; Ok(u)}).expect("Unable to construct path");
-        self.routes.push((route_matcher, Box::new(controller)));
+        match UriPathMatcher::new(route).and_then(|mut u| {u.append(controller.base_path())?; Ok(u)}) {
+            Ok(route_matcher) => self.routes.push((route_matcher, Box::new(controller))),
+            Err(reason) => self.fail(RouteError::InvalidPath { path: format!("{}{}", route, controller.base_path()), reason }),
+        }
+
+        self
+    }
+
+    /// Mount a whole router under a prefix, along with the middleware stack scoped to it. Requests whose path starts
+    /// with the prefix are handled by the mounted router once they went through the server middlewares and then the
+    /// mounted ones, whose paths are relative to the prefix, as are the routes of the mounted router.
+    /// # Example
+    /// ```rust,no_run
+    /// let admin_router = router::Builder::new().add(users_controller).build().expect("Invalid routes");
+    /// let admin_middlewares = middleware::Builder::new().apply(AuthMiddleware, vec!["/"], None).build();
+    ///
+    /// let router = router::Builder::new()
+    ///     .add(home_controller)
+    ///     .mount("/admin", admin_router, admin_middlewares)
+    ///     .build()
+    ///     .expect("Invalid routes");
+    /// ```
+    pub fn mount(mut self, prefix: &str, router: Router, middleware_stack: MiddlewareStack) -> Self {
+        match UriPathMatcher::new(prefix) {
+            Ok(mut prefix) => {
+                prefix.set_case_insensitive(router.case_insensitive);
+                self.mounts.push(Mount { prefix, router, middleware_stack });
+            }
+            Err(reason) => self.fail(RouteError::InvalidPath { path: prefix.to_string(), reason }),
+        }
+
+        self
+    }
+
+    /// Register the controllers serving a host, configured on their own router builder. The host pattern is either exact,
+    /// e.g. `api.example.com`, or has wildcard labels matching any single label, e.g. `*.example.com`, or named ones
+    /// whose value is added to the request captures, e.g. `<tenant>.example.com`. Exact hosts are tried first.
+    /// Requests whose host matches no pattern are handled by the default host if any, otherwise by the routes
+    /// registered outside of any host.
+    /// # Example
+    /// ```rust,no_run
+    /// let router = router::Builder::new()
+    ///     .host("www.example.com", |router| router.add(site_controller))
+    ///     .host("<tenant>.example.com", |router| router.add(tenant_controller))
+    ///     .default_host("www.example.com")
+    ///     .build()
+    ///     .expect("Invalid routes");
+    /// ```
+    pub fn host<F>(mut self, pattern: &str, config_fn: F) -> Self where F: FnOnce(Builder) -> Builder {
+        let host = match HostMatcher::new(pattern) {
+            Ok(host) => host,
+            Err(reason) => {
+                self.fail(RouteError::InvalidHost { pattern: pattern.to_string(), reason });
+                return self;
+            }
+        };
+
+        match config_fn(Builder::new()).build() {
+            Ok(router) => self.hosts.push((host, router)),
+            Err(e) => self.fail(e),
+        }
+        self
+    }
+
+    /// Set the host pattern handling the requests whose host matches no registered pattern, or without any host
+    pub fn default_host(mut self, pattern: &str) -> Self {
+        self.default_host = Some(pattern.to_string());
+        self
+    }
+
+    /// Register the controllers of an api version, configured on their own router builder. How the version of a request
+    /// is selected is set with `version_selector`, by default the version is the first segment of the path.
+    /// Names of the routes of a version are prefixed by the version when generating urls, e.g. `v2.read_user`.
+    /// # Example
+    /// ```rust,no_run
+    /// let router = router::Builder::new()
+    ///     .version("v1", |router| router.add(users_v1))
+    ///     .version("v2", |router| router.add(users_v2))
+    ///     .default_version("v2")
+    ///     .deprecate_version("v1")
+    ///     .build()
+    ///     .expect("Invalid routes");
+    /// ```
+    pub fn version<F>(mut self, version: &str, config_fn: F) -> Self where F: FnOnce(Builder) -> Builder {
+        match config_fn(Builder::new()).build() {
+            Ok(router) => self.versions.push((version.to_string(), router)),
+            Err(e) => self.fail(e),
+        }
+        self
+    }
+
+    /// Set how the api version of a request is selected
+    pub fn version_selector(mut self, selector: VersionSelector) -> Self {
+        self.version_selector = selector;
+        self
+    }
+
+    /// Set the version handling the requests which do not select any version
+    pub fn default_version(mut self, version: &str) -> Self {
+        self.default_version = Some(version.to_string());
+        self
+    }
+
+    /// Mark a version as deprecated, its responses get a `Deprecation: true` header
+    pub fn deprecate_version(mut self, version: &str) -> Self {
+        self.deprecated_versions.push(version.to_string());
+        self
+    }
 
+    /// Match the static path segments of every route regardless of case, e.g. `/Users/42` reaching `/users/<id>`.
+    /// The option only applies to the routes of this router: a case-insensitive router mounted in another one, or serving
+    /// one of its hosts or versions, leaves the other routes of the enclosing router case-sensitive. The middleware rules
+    /// are matched regardless of case for the requests reaching a case-insensitive route, so those routes cannot be
+    /// reached through a path escaping the rules.
+    /// Regex segments keep their own case sensitivity, see the `i` regex flag.
+    pub fn case_insensitive(mut self) -> Self {
+        self.case_insensitive = true;
         self
     }
 
-    /// Builds the router
-    pub fn build(self) -> Router {
+    /// Mount a debug controller answering `GET <path>` with the JSON list of every route of the router, including itself.
+    /// Each route is described by its `method`, `path`, `name`, `guards` and `controller`.
+    pub fn add_route_listing(mut self, path: &str) -> Self {
+        self.route_listing = Some(path.to_string());
+        self
+    }
+
+    /// Mount a controller answering `GET <path>` with the OpenAPI 3 JSON document of every route of the router.
+    /// The documentation endpoint itself is not part of the document.
+    #[cfg(feature = "openapi")]
+    pub fn add_openapi(mut self, path: &str, info: OpenApiInfo) -> Self {
+        self.openapi = Some((path.to_string(), info));
+        self
+    }
+
+    /// Builds the router. Controllers are tried in the order they were added, except those whose route ends with a tail
+    /// segment (e.g. a fallback controller mounted at `/<path..>`), which are tried last, the longest route first.
+    /// Fails on the first invalid path or host pattern registered, on an unknown default host or version, or on a route
+    /// name used more than once.
+    pub fn build(self) -> Result<Router, RouteError> {
         let Builder {
-            routes
+            mut routes,
+            mounts,
+            mut hosts,
+            default_host,
+            versions,
+            version_selector,
+            default_version,
+            deprecated_versions,
+            route_listing,
+            case_insensitive,
+            #[cfg(feature = "openapi")]
+            openapi,
+            error,
         } = self;
 
-        Router {
-            routes: Arc::new(routes),
+        if let Some(e) = error {
+            return Err(e);
+        }
+
+        routes.sort_by_key(|&(ref path_m, _)| tail_precedence(path_m));
+
+        if let Some(ref version) = default_version {
+            if !versions.iter().any(|&(ref v, _)| v == version) {
+                return Err(RouteError::UnknownVersion { version: version.clone() });
+            }
+        }
+
+        let versions = if versions.is_empty() {
+            None
+        } else {
+            let mut groups = Vec::with_capacity(versions.len());
+            for (version, router) in versions {
+                let mut prefix = UriPathMatcher::new(&version).map_err(|reason| RouteError::InvalidPath { path: version.clone(), reason })?;
+                prefix.set_case_insensitive(router.case_insensitive);
+                groups.push(VersionGroup {
+                    prefix,
+                    deprecated: deprecated_versions.contains(&version),
+                    version,
+                    router,
+                });
+            }
+
+            Some(Arc::new(ApiVersions {
+                groups,
+                selector: version_selector,
+                default_version,
+            }))
+        };
+
+        let mounts = Arc::new(mounts);
+
+        hosts.sort_by_key(|&(ref host, _)| host.wildcards());
+        let hosts = if hosts.is_empty() {
+            None
+        } else {
+            let default_host = match default_host {
+                Some(pattern) => {
+                    let host = HostMatcher::new(&pattern).map_err(|reason| RouteError::InvalidHost { pattern: pattern.clone(), reason })?.to_string();
+                    Some(hosts.iter().position(|&(ref h, _)| h.to_string() == host).ok_or(RouteError::UnknownHost { pattern })?)
+                }
+                None => None,
+            };
+
+            Some(Arc::new(VirtualHosts {
+                groups: hosts.into_iter().map(|(host, router)| HostGroup { host, router }).collect(),
+                default_host,
+            }))
+        };
+
+        if let Some(path) = route_listing {
+            let path_m = UriPathMatcher::new(&path).map_err(|reason| RouteError::InvalidPath { path: path.clone(), reason })?;
+
+            let mut infos = Router::list_routes(&routes, &mounts, &hosts, &versions, case_insensitive);
+            infos.push(RouteInfo {
+                method: Method::GET,
+                path: path_m.to_string(),
+                segments: path_m.iter().map(RouteSegment::from).collect(),
+                guards: Vec::new(),
+                controller: path.clone(),
+                name: None,
+                doc: None,
+                trailing_slash: false,
+                case_insensitive: false,
+                consumes: Vec::new(),
+                produces: Vec::new(),
+                version: None,
+                host: None,
+            });
+
+            let listing = BasicController::builder(&path, routes_to_json(&infos))
+                .add(Method::GET, "", |json: &String, _req: &SyncRequest, res: &mut SyncResponse| {
+                    res.status(StatusCode::OK).header(header::CONTENT_TYPE, "application/json").body(json.clone());
+                })
+                .build()?;
+
+            routes.push((path_m, Box::new(listing)));
         }
+
+        #[cfg(feature = "openapi")]
+        {
+            if let Some((path, info)) = openapi {
+                let path_m = UriPathMatcher::new(&path).map_err(|reason| RouteError::InvalidPath { path: path.clone(), reason })?;
+                let document = crate::openapi::generate(&info, &Router::list_routes(&routes, &mounts, &hosts, &versions, case_insensitive)).to_string();
+
+                let openapi_controller = BasicController::builder(&path, document)
+                    .add(Method::GET, "", |json: &String, _req: &SyncRequest, res: &mut SyncResponse| {
+                        res.status(StatusCode::OK).header(header::CONTENT_TYPE, "application/json").body(json.clone());
+                    })
+                    .build()?;
+
+                routes.push((path_m, Box::new(openapi_controller)));
+            }
+        }
+
+        // The generated controllers were added after sorting, they must not shadow the routes ending with a tail segment
+        routes.sort_by_key(|&(ref path_m, _)| tail_precedence(path_m));
+
+        // Routes of a host are resolved by the host router, whose route table is used for the requests it handles
+        let infos: Vec<RouteInfo> = Router::list_routes(&routes, &mounts, &hosts, &versions, case_insensitive)
+            .into_iter()
+            .filter(|info| info.host.is_none())
+            .collect();
+        let methods = infos.iter().filter_map(|info| UriPathMatcher::new(&info.path).ok().map(|mut path_m| {
+            path_m.set_case_insensitive(info.case_insensitive);
+            (path_m, info.method.clone())
+        })).collect();
+        let url_builder = UrlBuilder::new(&infos)?;
+        let paths = infos.iter().filter_map(|info| UriPathMatcher::new(&info.path).ok().map(|mut path_m| {
+            path_m.set_case_insensitive(info.case_insensitive);
+            path_m
+        })).collect();
+
+        Ok(Router {
+            routes: Arc::new(routes),
+            mounts,
+            hosts,
+            versions,
+            paths: Arc::new(paths),
+            methods: Arc::new(methods),
+            url_builder,
+            case_insensitive,
+        })
     }
 }
 
 /// A Struct responsible of dispatching request towards controllers
 pub struct Router {
     ///
-    routes: Arc<Vec<(UriPathMatcher, Box<Controller>)>>
+    routes: Arc<Vec<(UriPathMatcher, Box<Controller>)>>,
+    mounts: Arc<Vec<Mount>>,
+    hosts: Option<Arc<VirtualHosts>>,
+    versions: Option<Arc<ApiVersions>>,
+    /// The full path of every listed route, used to redirect requests on a trailing slash mismatch
+    paths: Arc<Vec<UriPathMatcher>>,
+    /// The full path and the method of every listed route outside of any host, used to tell which methods a path accepts
+    methods: Arc<Vec<(UriPathMatcher, Method)>>,
+    url_builder: UrlBuilder,
+    case_insensitive: bool,
 }
 
 impl Router {
@@ -77,11 +530,209 @@ impl Router {
     pub fn new() -> Self {
         Router {
             routes: Arc::new(Vec::new()),
+            mounts: Arc::new(Vec::new()),
+            hosts: None,
+            versions: None,
+            paths: Arc::new(Vec::new()),
+            methods: Arc::new(Vec::new()),
+            url_builder: UrlBuilder::default(),
+            case_insensitive: false,
         }
     }
 
+    /// List every route registered in the router, with its full path pattern
+    pub fn routes(&self) -> Vec<RouteInfo> {
+        Router::list_routes(&self.routes, &self.mounts, &self.hosts, &self.versions, self.case_insensitive)
+    }
+
+    /// Generate the url of the route named `name`, filling its variable segments with `params`
+    /// # Example
+    /// ```rust,no_run
+    /// let users = BasicController::builder("/users", ())
+    ///     .add(Method::GET, "/<id#r(^[0-9]+$)>", |_, _, _| {})
+    ///     .with_name("read_user")
+    ///     .build()
+    ///     .expect("Invalid routes");
+    ///
+    /// let router = router::Builder::new().add(users).build().expect("Invalid routes");
+    /// assert_eq!(router.url_for("read_user", &[("id", "42")]).unwrap(), "/users/42");
+    /// ```
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        self.url_builder.url_for(name, params)
+    }
+
+    /// Returns the url builder of the router, which can be kept to generate urls outside of request handling
+    pub fn url_builder(&self) -> UrlBuilder {
+        self.url_builder.clone()
+    }
+
+    /// Generate the OpenAPI 3 document of every route registered in the router
+    #[cfg(feature = "openapi")]
+    pub fn openapi(&self, info: &OpenApiInfo) -> ::serde_json::Value {
+        crate::openapi::generate(info, &self.routes())
+    }
+
+    fn list_routes(routes: &[(UriPathMatcher, Box<Controller>)], mounts: &[Mount], hosts: &Option<Arc<VirtualHosts>>, versions: &Option<Arc<ApiVersions>>, case_insensitive: bool) -> Vec<RouteInfo> {
+        let prefixed = |path_m: &UriPathMatcher, infos: Vec<RouteInfo>| {
+            let prefix = path_m.to_string();
+            let prefix_segments: Vec<RouteSegment> = path_m.iter().map(RouteSegment::from).collect();
+            infos.into_iter().map(move |mut info| {
+                info.path = join_route_path(&prefix, &info);
+                info.segments = prefix_segments.iter().cloned().chain(info.segments.into_iter()).collect();
+                info
+            })
+        };
+
+        let mut infos: Vec<RouteInfo> = mounts.iter()
+            .flat_map(|mount| prefixed(&mount.prefix, mount.router.routes()))
+            .chain(routes.iter().flat_map(|&(ref path_m, ref controller)| prefixed(path_m, controller.routes())))
+            .collect();
+
+        if let Some(ref hosts) = hosts {
+            for group in &hosts.groups {
+                infos.extend(group.router.routes().into_iter().map(|mut info| {
+                    info.host = Some(group.host.to_string());
+                    info
+                }));
+            }
+        }
+
+        if let Some(ref versions) = versions {
+            for group in &versions.groups {
+                let path_prefixed = versions.selector == VersionSelector::PathPrefix;
+                infos.extend(group.router.routes().into_iter().map(|mut info| {
+                    if path_prefixed {
+                        info.path = join_route_path(&format!("/{}", group.version), &info);
+                        info.segments.insert(0, RouteSegment::Static(group.version.clone()));
+                    }
+                    info.name = info.name.map(|name| format!("{}.{}", group.version, name));
+                    info.version = Some(group.version.clone());
+                    info
+                }));
+            }
+        }
+
+        for info in infos.iter_mut() {
+            info.case_insensitive |= case_insensitive;
+        }
+
+        infos
+    }
+
+    /// Whether a controller of the router matches the start of the request path, without consuming it
+    pub(crate) fn matches(&self, req: &SyncRequest) -> bool {
+        let case_insensitive = self.case_insensitive || req.case_insensitive_paths();
+        self.mounts.iter().any(|mount| mount.prefix.match_segments(req.current_path(), case_insensitive).is_some())
+            || self.routes.iter().any(|&(ref path_m, _)| path_m.match_segments(req.current_path(), case_insensitive).is_some())
+    }
+
+    /// The location a request should be redirected to when the path only matches a route once its trailing slash is toggled
+    fn trailing_slash_redirect(&self, req: &SyncRequest) -> Option<String> {
+        let path = req.current_path();
+        let trailing_slash = req.has_trailing_slash();
+
+        let has_route = |with_slash: bool| self.paths.iter().any(|p| {
+            p.trailing_slash() == with_slash && p.match_segments(path, req.case_insensitive_paths()) == Some(path.len())
+        });
+
+        if path.is_empty() || has_route(trailing_slash) || !has_route(!trailing_slash) {
+            return None;
+        }
+
+        let uri_path = req.uri().path();
+        let mut location = if trailing_slash {
+            uri_path.trim_end_matches('/').to_string()
+        } else {
+            format!("{}/", uri_path)
+        };
+
+        if let Some(query) = req.uri().query() {
+            location.push('?');
+            location.push_str(query);
+        }
+
+        Some(location)
+    }
+
+    /// Prepare a request to be handled by the router, before it goes through the middlewares
+    pub(crate) fn prepare(&self, req: &mut SyncRequest) {
+        // The routes of a path are those of the host the request is dispatched to, if any
+        let methods = match self.hosts.as_ref().and_then(|hosts| hosts.select(req)) {
+            Some((group, _)) => group.router.methods.clone(),
+            None => self.methods.clone(),
+        };
+
+        // The middlewares are matched with the case rules of the route reached by the request
+        let path = req.current_path();
+        let reaches_case_insensitive = methods.iter()
+            .any(|&(ref path_m, _)| path_m.case_insensitive() && path_m.match_segments(path, false) == Some(path.len()));
+
+        if self.case_insensitive || reaches_case_insensitive {
+            req.set_case_insensitive_paths();
+        }
+        req.set_route_methods(&methods);
+    }
+
     ///
     pub fn dispatch(&self, req: &mut SyncRequest, res: &mut SyncResponse) {
+        req.set_url_builder(&self.url_builder);
+        self.prepare(req);
+
+        if let Some(ref hosts) = self.hosts {
+            if let Some((group, captures)) = hosts.select(req) {
+                for (name, value) in captures {
+                    req.add_capture(name, value);
+                }
+
+                req.replace_url_builder(&group.router.url_builder);
+                group.router.dispatch(req, res);
+                return;
+            }
+        }
+
+        if req.trailing_slash_policy() == TrailingSlash::Redirect {
+            if let Some(location) = self.trailing_slash_redirect(req) {
+                res.status(StatusCode::PERMANENT_REDIRECT).header(header::LOCATION, location.as_str());
+                return;
+            }
+        }
+
+        self.route(req, res);
+    }
+
+    /// Dispatch the request to the version or controller matching its path
+    fn route(&self, req: &mut SyncRequest, res: &mut SyncResponse) {
+        if let Some(ref versions) = self.versions {
+            match versions.select(req) {
+                Ok(Some(group)) => {
+                    match versions.selector {
+                        VersionSelector::Header(ref name) => { res.header(header::VARY, name.as_str()); }
+                        VersionSelector::MediaType(_) => { res.header(header::VARY, "Accept"); }
+                        VersionSelector::PathPrefix => {}
+                    }
+
+                    if group.deprecated {
+                        res.header("Deprecation", "true");
+                    }
+
+                    group.router.prepare(req);
+                    group.router.route(req, res);
+                    return;
+                }
+                Ok(None) => {}
+                Err(status) => {
+                    res.status(status);
+                    return;
+                }
+            }
+        }
+
+        if let Some(mount) = self.mounts.iter().find(|mount| req.current_path_match(&mount.prefix)) {
+            mount.router.prepare(req);
+            mount.middleware_stack.handle(req, res, |req, res| mount.router.route(req, res));
+            return;
+        }
+
         let h: Option<(usize, &(UriPathMatcher, Box<Controller>))> = self.routes.iter().enumerate().find(
             |&(_, &(ref re, _))| {
                 req.current_path_match(re)
@@ -100,6 +751,138 @@ impl Clone for Router {
     fn clone(&self) -> Self {
         Router {
             routes: self.routes.clone(),
+            mounts: self.mounts.clone(),
+            hosts: self.hosts.clone(),
+            versions: self.versions.clone(),
+            paths: self.paths.clone(),
+            methods: self.methods.clone(),
+            url_builder: self.url_builder.clone(),
+            case_insensitive: self.case_insensitive,
+        }
+    }
+}
+
+/// Generates urls out of route names, see `controller::Builder::with_name`
+#[derive(Debug, Clone, Default)]
+pub struct UrlBuilder {
+    routes: Arc<HashMap<String, UrlRoute>>,
+}
+
+#[derive(Debug)]
+struct UrlRoute {
+    segments: Vec<UrlSegment>,
+    trailing_slash: bool,
+}
+
+#[derive(Debug)]
+enum UrlSegment {
+    Static(String),
+    Variable { name: Option<String>, regex: Option<Regex>, optional: bool },
+    Tail { name: Option<String> },
+}
+
+impl UrlBuilder {
+    fn new(routes: &[RouteInfo]) -> Result<Self, RouteError> {
+        let mut named = HashMap::new();
+
+        for route in routes {
+            if let Some(ref name) = route.name {
+                let segments = route.segments.iter().map(|segment| match segment {
+                    RouteSegment::Static(ref s) => UrlSegment::Static(s.clone()),
+                    RouteSegment::Variable { ref name, ref pattern, optional } => UrlSegment::Variable {
+                        name: name.clone(),
+                        regex: pattern.as_ref().map(|p| Regex::new(p).expect("Route regexes are validated when building controllers")),
+                        optional: *optional,
+                    },
+                    RouteSegment::Tail { ref name } => UrlSegment::Tail { name: name.clone() },
+                }).collect();
+
+                if named.insert(name.clone(), UrlRoute { segments, trailing_slash: route.trailing_slash }).is_some() {
+                    return Err(RouteError::DuplicateName { name: name.clone() });
+                }
+            }
         }
+
+        Ok(UrlBuilder {
+            routes: Arc::new(named),
+        })
+    }
+
+    /// Generate the url of the route named `name`, filling its variable segments with `params`.
+    /// Values are validated against the segment regex, if any, then percent-encoded. Optional segments without a value
+    /// are left out of the url.
+    pub fn url_for(&self, name: &str, params: &[(&str, &str)]) -> Result<String, UrlError> {
+        let route = self.routes.get(name).ok_or_else(|| UrlError::UnknownRoute(name.to_string()))?;
+
+        let mut url = String::new();
+
+        for segment in &route.segments {
+            if let UrlSegment::Variable { name: Some(ref segment_name), optional: true, .. } = segment {
+                // Optional segments being the last ones, the url ends at the first one left out
+                if !params.iter().any(|&(n, _)| n == segment_name) {
+                    break;
+                }
+            }
+
+            url.push('/');
+
+            match segment {
+                UrlSegment::Static(ref s) => url.push_str(s),
+                UrlSegment::Variable { name: None, .. } | UrlSegment::Tail { name: None } => return Err(UrlError::UnnamedSegment(name.to_string())),
+                UrlSegment::Tail { name: Some(ref segment_name) } => {
+                    let value = params.iter().find(|&&(n, _)| n == segment_name).map(|&(_, v)| v)
+                        .ok_or_else(|| UrlError::MissingParameter { route: name.to_string(), name: segment_name.clone() })?;
+
+                    // The tail value spans many segments, each one being encoded separately
+                    let encoded: Vec<String> = value.split('/').map(|part| utf8_percent_encode(part, PATH_SEGMENT_ENCODE_SET).to_string()).collect();
+                    url.push_str(encoded.join("/").trim_start_matches('/'));
+                }
+                UrlSegment::Variable { name: Some(ref segment_name), ref regex, .. } => {
+                    let value = params.iter().find(|&&(n, _)| n == segment_name).map(|&(_, v)| v)
+                        .ok_or_else(|| UrlError::MissingParameter { route: name.to_string(), name: segment_name.clone() })?;
+
+                    if regex.as_ref().map(|r| !r.is_match(value)).unwrap_or(false) {
+                        return Err(UrlError::InvalidParameter { route: name.to_string(), name: segment_name.clone(), value: value.to_string() });
+                    }
+
+                    url.extend(utf8_percent_encode(value, PATH_SEGMENT_ENCODE_SET));
+                }
+            }
+        }
+
+        if url.is_empty() || route.trailing_slash {
+            url.push('/');
+        }
+
+        Ok(url)
     }
-}
\ No newline at end of file
+}
+
+fn join_paths(prefix: &str, path: &str) -> String {
+    match (prefix.trim_end_matches('/'), path.trim_start_matches('/')) {
+        ("", "") => "/".to_string(),
+        (p, "") => p.to_string(),
+        (p, s) => format!("{}/{}", p, s),
+    }
+}
+
+/// Join a prefix to the path of a route, keeping the trailing slash of the route, e.g. the root `/` of a controller `/users`
+fn join_route_path(prefix: &str, info: &RouteInfo) -> String {
+    let mut path = join_paths(prefix, &info.path);
+    if info.trailing_slash && !path.ends_with('/') {
+        path.push('/');
+    }
+    path
+}
+
+fn routes_to_json(routes: &[RouteInfo]) -> String {
+    let entries: Vec<String> = routes.iter().map(|r| {
+        let guards: Vec<String> = r.guards.iter().map(|g| format!("\"{}\"", json_escape(g))).collect();
+        let name = r.name.as_ref().map(|n| format!("\"{}\"", json_escape(n))).unwrap_or_else(|| "null".to_string());
+        let host = r.host.as_ref().map(|h| format!("\"{}\"", json_escape(h))).unwrap_or_else(|| "null".to_string());
+        format!("{{\"method\":\"{}\",\"host\":{},\"path\":\"{}\",\"name\":{},\"guards\":[{}],\"controller\":\"{}\"}}",
+                json_escape(r.method.as_str()), host, json_escape(&r.path), name, guards.join(","), json_escape(&r.controller))
+    }).collect();
+
+    format!("[{}]", entries.join(","))
+}
@@ -1,15 +1,20 @@
 use futures::Future;
+use futures::future::Either;
 use futures::sync::oneshot::{Sender, channel};
 use hyper::service::service_fn;
 use log::{info, error, warn};
 use tokio::runtime::TaskExecutor;
 
 use crate::http::*;
-use crate::utils;
-use crate::error::ServerError;
+use crate::error::{BodyError, RouteError, ServerError};
 use crate::middleware::{MiddlewareStack, Builder as MidStackBuilder};
-use crate::router::{Router, Builder as RouterBuilder};
+use crate::router::{Router, Builder as RouterBuilder, TrailingSlash};
+use crate::cookies::CookieKeys;
+#[cfg(feature = "compression")]
+use crate::compression::Compression;
 use threadpool::ThreadPool;
+use arc_swap::ArcSwap;
+use std::sync::Arc;
 use tokio::prelude::stream::Stream;
 
 ///
@@ -21,6 +26,7 @@ pub struct ListenerBuilder {
     uri: Option<String>,
     cert_path: Option<String>,
     key_path: Option<String>,
+    host_certs: Vec<(String, String, String)>,
     thread_pool_size: Option<usize>,
 }
 
@@ -32,6 +38,7 @@ impl ListenerBuilder {
             uri: None,
             cert_path: None,
             key_path: None,
+            host_certs: Vec::new(),
             thread_pool_size: None
         }
     }
@@ -62,6 +69,14 @@ impl ListenerBuilder {
         self
     }
 
+    /// Set the ssl certificates files presented to clients requesting a host matching `host_pattern` through SNI,
+    /// with the same patterns as `router::Builder::host`. Other clients are presented the certificates set with
+    /// `set_ssl_certificates`.
+    pub fn add_host_ssl_certificates(mut self, host_pattern: &str, cert_path: &str, key_path: &str) -> Self {
+        self.host_certs.push((host_pattern.to_string(), cert_path.to_string(), key_path.to_string()));
+        self
+    }
+
     /// Builds a new Listener Configuration
     pub fn build(self) -> ListenerConfig {
         let ListenerBuilder {
@@ -69,6 +84,7 @@ impl ListenerBuilder {
             uri,
             cert_path,
             key_path,
+            host_certs,
             thread_pool_size,
         } = self;
 
@@ -77,6 +93,7 @@ impl ListenerBuilder {
             uri,
             cert_path,
             key_path,
+            host_certs,
             thread_pool_size
         }
     }
@@ -88,6 +105,7 @@ pub struct ListenerConfig {
     uri: Option<String>,
     cert_path: Option<String>,
     key_path: Option<String>,
+    host_certs: Vec<(String, String, String)>,
     thread_pool_size: Option<usize>,
 }
 
@@ -103,6 +121,7 @@ mod listener_config_ext {
                 uri: None,
                 cert_path: None,
                 key_path: None,
+                host_certs: Vec::new(),
                 thread_pool_size: None
             }
         }
@@ -116,17 +135,70 @@ mod listener_config_ext {
         pub fn ssl_files_path(&self) -> (Option<String>, Option<String>) {
             (self.cert_path.clone(), self.key_path.clone())
         }
+
+        #[doc(hidden)]
+        pub fn host_ssl_files_paths(&self) -> Vec<(String, String, String)> {
+            self.host_certs.clone()
+        }
+    }
+}
+
+/// Handle to swap the router and the middleware stack of a running server.
+/// Requests received after a swap are handled by the new router and middleware stack, while in-flight requests
+/// finish on the ones they started with.
+#[derive(Clone)]
+pub struct ReloadHandle {
+    pipeline: Arc<ArcSwap<RequestPipeline>>,
+}
+
+impl ReloadHandle {
+    /// Atomically install a new router and middleware stack, returning the previous ones
+    pub fn swap(&self, router: Router, middleware_stack: MiddlewareStack) -> (Router, MiddlewareStack) {
+        let previous = self.pipeline.swap(Arc::new(RequestPipeline { router, middleware_stack }));
+        info!("Router and middleware stack reloaded");
+        (previous.router.clone(), previous.middleware_stack.clone())
+    }
+
+    /// Install a new router, keeping the current middleware stack, returning the previous router
+    pub fn swap_router(&self, router: Router) -> Router {
+        let previous = self.pipeline.rcu(|current| RequestPipeline { router: router.clone(), middleware_stack: current.middleware_stack.clone() });
+        info!("Router reloaded");
+        previous.router.clone()
+    }
+
+    /// Install a new middleware stack, keeping the current router, returning the previous middleware stack
+    pub fn swap_middlewares(&self, middleware_stack: MiddlewareStack) -> MiddlewareStack {
+        let previous = self.pipeline.rcu(|current| RequestPipeline { router: current.router.clone(), middleware_stack: middleware_stack.clone() });
+        info!("Middleware stack reloaded");
+        previous.middleware_stack.clone()
+    }
+
+    /// Same as `swap_router`, but building the new router with the provided closure. The current router is kept when
+    /// the new one is invalid.
+    pub fn reconfigure_router<F>(&self, config_fn: F) -> Result<Router, RouteError> where F: FnOnce(RouterBuilder) -> RouterBuilder {
+        Ok(self.swap_router(config_fn(RouterBuilder::new()).build()?))
+    }
+
+    /// Same as `swap_middlewares`, but building the new middleware stack with the provided closure
+    pub fn reconfigure_middlewares<F>(&self, config_fn: F) -> MiddlewareStack where F: FnOnce(MidStackBuilder) -> MidStackBuilder {
+        self.swap_middlewares(config_fn(MidStackBuilder::new()).build())
     }
 }
 
 /// Handle to signal the server on termination
 pub struct ServerSpawn {
     tx: Option<Sender<()>>,
+    reload_handle: ReloadHandle,
     #[cfg(feature = "request_handler")]
     handler: HttpService,
 }
 
 impl ServerSpawn {
+    /// Retrieve a handle to swap the router and the middleware stack of the running server
+    pub fn reload_handle(&self) -> ReloadHandle {
+        self.reload_handle.clone()
+    }
+
     /// Signal the server to terminate itself gracefully
     pub fn terminate(mut self) {
         if let Some(s) = self.tx.take(){
@@ -144,8 +216,16 @@ impl ServerSpawn {
 /// Builder for the Server type
 pub struct Builder {
     middleware_stack: Option<MiddlewareStack>,
-    router: Option<Router>,
+    router: Option<Result<Router, RouteError>>,
     listener_config: Option<ListenerConfig>,
+    app_state: Extensions,
+    trailing_slash: TrailingSlash,
+    path_decoding: PathDecoding,
+    path_normalization: PathNormalization,
+    body_loading: BodyLoading,
+    cookie_keys: Option<CookieKeys>,
+    #[cfg(feature = "compression")]
+    compression: Option<Compression>,
 }
 
 impl Builder {
@@ -155,11 +235,20 @@ impl Builder {
             middleware_stack: None,
             router: None,
             listener_config: None,
+            app_state: Extensions::new(),
+            trailing_slash: TrailingSlash::default(),
+            path_decoding: PathDecoding::default(),
+            path_normalization: PathNormalization::default(),
+            body_loading: BodyLoading::new(),
+            cookie_keys: None,
+            #[cfg(feature = "compression")]
+            compression: None,
         }
     }
 
     /// This method will call the provided closure with a mutable ref of the router
     /// Once into the closure it is possible to add controllers to the router.
+    /// An invalid router is reported when running the server, see `router::Builder::build`.
     pub fn configure_router<F>(mut self, config_fn: F) -> Self where F: Fn(RouterBuilder) -> RouterBuilder {
         self.router = Some(config_fn(RouterBuilder::new()).build());
         self
@@ -179,24 +268,110 @@ impl Builder {
         self
     }
 
+    /// Register a typed application-wide state entry, e.g. a database pool or the configuration.
+    /// Every handler, guard and middleware can then retrieve it with `SyncRequest::app_state`.
+    /// Registering a second entry of the same type replaces the first one.
+    pub fn with_state<T: Send + Sync + 'static>(mut self, state: T) -> Self {
+        self.app_state.insert(state);
+        self
+    }
+
+    /// Set how routes handle a request path ending with a slash, trailing slashes are ignored by default.
+    /// With `TrailingSlash::Strict` or `TrailingSlash::Redirect`, a route declared as `/users/` differs from `/users`: the delegate
+    /// `/` of a controller `/users` is `/users/`, and the delegate `` is `/users`.
+    pub fn trailing_slash(mut self, policy: TrailingSlash) -> Self {
+        self.trailing_slash = policy;
+        self
+    }
+
+    /// Set how requests with an encoded `/` or invalid UTF-8 in a path segment are handled, such requests are rejected
+    /// with `400 Bad Request` by default. Other segments are always percent-decoded before being matched and captured.
+    pub fn path_decoding(mut self, policy: PathDecoding) -> Self {
+        self.path_decoding = policy;
+        self
+    }
+
+    /// Set how requests with a non canonical path (`//`, `/./` or `/../`) are handled, such paths are normalized by default.
+    /// Middlewares and routes are always matched against the same path.
+    pub fn path_normalization(mut self, policy: PathNormalization) -> Self {
+        self.path_normalization = policy;
+        self
+    }
+
+    /// Answer the requests whose body is larger than `max_size` bytes, once decoded, with `413 Payload Too Large`.
+    /// Bodies of any size are accepted by default.
+    pub fn max_body_size(mut self, max_size: u64) -> Self {
+        self.body_loading = self.body_loading.max_size(max_size);
+        self
+    }
+
+    /// Decode the request bodies encoded with the gzip, deflate or br content codings before handling the requests.
+    /// Requests with other codings are answered with `415 Unsupported Media Type`, and those whose body cannot be
+    /// decoded with `400 Bad Request`. Decoded bodies are limited to `DEFAULT_MAX_DECODED_SIZE` bytes unless a
+    /// `max_body_size` is set. Bodies are handled as they were received by default.
+    #[cfg(feature = "compression")]
+    pub fn decompress_request_bodies(mut self) -> Self {
+        self.body_loading = self.body_loading.decompress();
+        self
+    }
+
+    /// Set the keys signing and encrypting the cookies, see `CookieKeys`. Without keys, signed and private cookies can
+    /// neither be set nor read.
+    pub fn cookie_keys(mut self, keys: CookieKeys) -> Self {
+        self.cookie_keys = Some(keys);
+        self
+    }
+
+    /// Compress the responses whose client accepts it, see `Compression`. Responses are not compressed by default.
+    #[cfg(feature = "compression")]
+    pub fn compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
     /// Converts the builder into the Server type
     pub fn build(self) -> Server {
         let Builder {
             middleware_stack,
             router,
             listener_config,
+            app_state,
+            trailing_slash,
+            path_decoding,
+            path_normalization,
+            body_loading,
+            cookie_keys,
+            #[cfg(feature = "compression")]
+            compression,
         } = self;
 
         let listener_config = listener_config.unwrap_or_else(|| ListenerConfig::new());
 
+        let (router, router_error) = match router {
+            Some(Ok(router)) => (router, None),
+            Some(Err(e)) => (Router::new(), Some(e)),
+            None => (Router::new(), None),
+        };
+
         Server {
             service: HttpService {
-                router: router.unwrap_or_else(|| Router::new()),
-                middleware_stack: middleware_stack.unwrap_or_else(|| MiddlewareStack::new()),
+                pipeline: Arc::new(ArcSwap::from(Arc::new(RequestPipeline {
+                    router,
+                    middleware_stack: middleware_stack.unwrap_or_else(|| MiddlewareStack::new()),
+                }))),
+                app_state: AppState::new(app_state),
+                trailing_slash,
+                path_decoding,
+                path_normalization,
+                body_loading,
+                cookie_keys: cookie_keys.map(Arc::new),
+                #[cfg(feature = "compression")]
+                compression: compression.map(Arc::new),
                 request_timeout: listener_config.request_timeout_ms,
                 thread_pool: ThreadPool::new(listener_config.thread_pool_size.unwrap_or_else(|| num_cpus::get())),
             },
-            listener_config
+            listener_config,
+            router_error,
         }
     }
 }
@@ -205,6 +380,7 @@ impl Builder {
 pub struct Server {
     service: HttpService,
     listener_config: ListenerConfig,
+    router_error: Option<RouteError>,
 }
 
 impl Server {
@@ -219,8 +395,19 @@ impl Server {
         &self.service
     }
 
+    /// Retrieve a handle to swap the router and the middleware stack once the server is running
+    pub fn reload_handle(&self) -> ReloadHandle {
+        ReloadHandle {
+            pipeline: self.service.pipeline.clone(),
+        }
+    }
+
     /// Spawn the server inside the provided executor and return a ServerSpawn context to explicitly terminate it.
     pub fn spawn(&self, executor: TaskExecutor) -> Result<ServerSpawn, crate::error::ServerError> {
+        if let Some(ref e) = self.router_error {
+            return Err(ServerError::InvalidRoutes(e.clone()));
+        }
+
         let uri: Uri = self.listener_config.uri()
             .expect("Fatal Error: No uri provided.\n You can fix this error by calling Server::set_uri or by configuring the listener with Server::configure_listener")
             .parse()?;
@@ -236,6 +423,7 @@ impl Server {
 
         let server_spawn = ServerSpawn {
             tx: Some(sender),
+            reload_handle: self.reload_handle(),
             #[cfg(feature = "request_handler")]
             handler: service.clone(),
         };
@@ -257,6 +445,7 @@ impl Server {
         } else if scheme.eq(&crate::http_types::uri::Scheme::HTTPS) {
             #[cfg(feature = "https")]
                 {
+                    let host_certs = self.listener_config.host_ssl_files_paths();
                     if let (Some(cert_path), Some(key_path)) = self.listener_config.ssl_files_path() {
                         use std::sync::Arc;
                         use futures::Stream;
@@ -266,7 +455,11 @@ impl Server {
                         let certs = load_certs(cert_path.as_ref());
                         let key = load_private_key(key_path.as_ref());
                         let mut cfg = ::rustls::ServerConfig::new(::rustls::NoClientAuth::new());
-                        let _ = cfg.set_single_cert(certs, key);
+                        if host_certs.is_empty() {
+                            let _ = cfg.set_single_cert(certs, key);
+                        } else {
+                            cfg.cert_resolver = Arc::new(HostCertResolver::new(certified_key(certs, key), &host_certs));
+                        }
                         let arc_config = Arc::new(cfg);
 
                         let acceptor = TlsAcceptor::from(arc_config);
@@ -325,11 +518,24 @@ impl Server {
     }
 }
 
+/// The router and middleware stack a request goes through, each request holding the one it started with
+struct RequestPipeline {
+    router: Router,
+    middleware_stack: MiddlewareStack,
+}
+
 #[doc(hidden)]
 #[derive(Clone)]
 pub struct HttpService {
-    router: Router,
-    middleware_stack: MiddlewareStack,
+    pipeline: Arc<ArcSwap<RequestPipeline>>,
+    app_state: AppState,
+    trailing_slash: TrailingSlash,
+    path_decoding: PathDecoding,
+    path_normalization: PathNormalization,
+    body_loading: BodyLoading,
+    cookie_keys: Option<Arc<CookieKeys>>,
+    #[cfg(feature = "compression")]
+    compression: Option<Arc<Compression>>,
     request_timeout: u64,
     thread_pool: ThreadPool,
 }
@@ -338,25 +544,73 @@ pub struct HttpService {
 impl HttpService {
     pub fn handle(&self, req: Request<Body>) -> Box<Future<Item=Response<Body>, Error=ServerError> + Send> {
         use std::time::{Instant, Duration};
-        use crate::server::utils::RequestContinuation::*;
         use futures::sync::oneshot::channel;
 
         let (tx, rx) = channel();
 
         let HttpService {
-            router,
-            middleware_stack,
+            pipeline,
+            app_state,
+            trailing_slash,
+            path_decoding,
+            path_normalization,
+            body_loading,
+            cookie_keys,
+            #[cfg(feature = "compression")]
+            compression,
             request_timeout,
             thread_pool,
         } = self.clone();
 
-        Box::new(req.load_body().map_err(|e| ServerError::from(e)).and_then(move |mut request| {
+        let loaded = body_loading.load(req).then(|loaded| match loaded {
+            Ok(request) => Ok(Ok(request)),
+            Err(BodyError::HyperError(e)) => Err(ServerError::from(e)),
+            Err(e) => Ok(Err(e)),
+        });
+
+        Box::new(loaded.and_then(move |loaded| {
+            let mut request = match loaded {
+                Ok(request) => request,
+                Err(e) => {
+                    warn!("Unable to load the request body: {}", e);
+                    let mut resp = Response::new(Body::empty());
+                    *resp.status_mut() = e.status();
+                    return Either::B(futures::future::ok(resp));
+                }
+            };
+
+            request.set_app_state(app_state);
+            request.set_cookie_keys(cookie_keys.clone());
+            request.set_trailing_slash_policy(trailing_slash);
+
+            // The pipeline is resolved once per request, so a reload never affects an in-flight request
+            let pipeline = pipeline.load();
+
             thread_pool.execute(move || {
                 let req_iat = Instant::now();
                 let mut response = SyncResponse::new();
+                response.set_cookie_keys(cookie_keys);
+
+                let RequestPipeline { ref router, ref middleware_stack } = *pipeline;
+
+                #[cfg(feature = "compression")]
+                let body_error = body_loading.decode(&mut request).err();
+                #[cfg(not(feature = "compression"))]
+                let body_error: Option<BodyError> = None;
 
-                if let Continue = middleware_stack.resolve(&mut request, &mut response) {
-                    router.dispatch(&mut request, &mut response);
+                let valid_path = match request.normalize_path() {
+                    Some(canonical) => canonical || path_normalization == PathNormalization::Normalize,
+                    None => false,
+                };
+
+                if let Some(e) = body_error {
+                    warn!("Unable to decode the request body: {}", e);
+                    response.status(e.status());
+                } else if !valid_path || (path_decoding == PathDecoding::Reject && request.has_undecodable_path()) {
+                    response.status(StatusCode::BAD_REQUEST);
+                } else {
+                    router.prepare(&mut request);
+                    middleware_stack.handle(&mut request, &mut response, |req, res| router.dispatch(req, res));
                 }
 
                 let final_res = response.build_response().unwrap_or_else(|_| {
@@ -366,6 +620,12 @@ impl HttpService {
                     res
                 });
 
+                #[cfg(feature = "compression")]
+                let final_res = match compression {
+                    Some(ref compression) => compression.compress(&request, final_res),
+                    None => final_res,
+                };
+
                 let resp_status = final_res.status();
 
                 let _ = tx.send(final_res);
@@ -397,10 +657,10 @@ impl HttpService {
                 Box::new(futures::empty::<Response<Body>, ServerError>()) as Box<Future<Item=Response<Body>, Error=ServerError> + Send>
             };
 
-            rx.map_err(|e| ServerError::from(e))
+            Either::A(rx.map_err(|e| ServerError::from(e))
                 .select(timeout)
                 .map(|(r, _)| r)
-                .map_err(|(e, _)| e)
+                .map_err(|(e, _)| e))
         }))
     }
 }
@@ -409,8 +669,45 @@ impl HttpService {
 #[cfg(feature = "https")]
 mod ssl_loading_utils {
     use rustls;
+    use rustls::sign::{self, CertifiedKey};
     use std::fs;
     use std::io::BufReader;
+    use std::sync::Arc;
+    use crate::utils::HostMatcher;
+
+    /// Selects the certificates presented to a client from the host it requested through SNI
+    pub struct HostCertResolver {
+        default: CertifiedKey,
+        hosts: Vec<(HostMatcher, CertifiedKey)>,
+    }
+
+    impl HostCertResolver {
+        pub fn new(default: CertifiedKey, host_certs: &[(String, String, String)]) -> Self {
+            let mut hosts: Vec<(HostMatcher, CertifiedKey)> = host_certs.iter().map(|&(ref pattern, ref cert_path, ref key_path)| {
+                let host = HostMatcher::new(pattern).expect("Unable to construct certificate host pattern");
+                (host, certified_key(load_certs(cert_path), load_private_key(key_path)))
+            }).collect();
+            hosts.sort_by_key(|&(ref host, _)| host.wildcards());
+
+            HostCertResolver { default, hosts }
+        }
+    }
+
+    impl rustls::ResolvesServerCert for HostCertResolver {
+        fn resolve(&self, server_name: Option<webpki::DNSNameRef>, _sigschemes: &[rustls::SignatureScheme]) -> Option<CertifiedKey> {
+            let key = server_name.map(|name| -> &str { name.into() })
+                .and_then(|name| self.hosts.iter().find(|&&(ref host, _)| host.match_host(name).is_some()))
+                .map(|&(_, ref key)| key)
+                .unwrap_or(&self.default);
+
+            Some(key.clone())
+        }
+    }
+
+    pub fn certified_key(certs: Vec<rustls::Certificate>, key: rustls::PrivateKey) -> CertifiedKey {
+        let signing_key = sign::any_supported_type(&key).expect("Unable to load private key: unsupported key type");
+        CertifiedKey::new(certs, Arc::new(signing_key))
+    }
 
     pub fn load_certs(filename: &str) -> Vec<rustls::Certificate> {
         let certfile = fs::File::open(filename).expect("cannot open certificate file");
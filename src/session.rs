@@ -0,0 +1,430 @@
+use std::fmt::Write;
+use std::str::FromStr;
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime};
+
+use hashbrown::HashMap;
+use log::error;
+use parking_lot::{Mutex, RwLock};
+use ring::rand::{SecureRandom, SystemRandom};
+
+use crate::cookies::{Cookie, SameSite};
+use crate::error::SessionError;
+use crate::http::*;
+use crate::middleware::Middleware;
+use crate::utils::RequestContinuation;
+
+/// The persisted state of a session
+#[derive(Debug, Clone)]
+pub struct SessionRecord {
+    /// The session values
+    pub values: HashMap<String, String>,
+    /// When the session was created, rotating its id keeps it
+    pub created: SystemTime,
+    /// When the session was last used by a request
+    pub last_access: SystemTime,
+}
+
+/// The trait a struct need to `impl` to store the sessions, e.g. in a database or a cache server
+pub trait SessionStore: Send + Sync {
+    /// Load the session of id `id`, none if there is no such session
+    fn load(&self, id: &str) -> Result<Option<SessionRecord>, SessionError>;
+
+    /// Save the session of id `id`, the store may discard it once `ttl` has elapsed
+    fn save(&self, id: &str, record: &SessionRecord, ttl: Duration) -> Result<(), SessionError>;
+
+    /// Remove the session of id `id`, if any
+    fn remove(&self, id: &str) -> Result<(), SessionError>;
+}
+
+/// A session store keeping the sessions in memory, expired sessions are evicted as new ones are saved
+pub struct MemoryStore {
+    sessions: RwLock<HashMap<String, (SessionRecord, Instant)>>,
+    last_eviction: Mutex<Instant>,
+    eviction_interval: Duration,
+}
+
+impl MemoryStore {
+    /// Create an empty memory store, evicting the expired sessions at most once a minute
+    pub fn new() -> Self {
+        MemoryStore {
+            sessions: RwLock::new(HashMap::new()),
+            last_eviction: Mutex::new(Instant::now()),
+            eviction_interval: Duration::from_secs(60),
+        }
+    }
+
+    /// Set how often the expired sessions are evicted
+    pub fn eviction_interval(mut self, interval: Duration) -> Self {
+        self.eviction_interval = interval;
+        self
+    }
+
+    /// The number of sessions stored, including the expired ones not evicted yet
+    pub fn len(&self) -> usize {
+        self.sessions.read().len()
+    }
+
+    fn evict_expired(&self) {
+        let now = Instant::now();
+        {
+            let mut last_eviction = self.last_eviction.lock();
+            if now.duration_since(*last_eviction) < self.eviction_interval {
+                return;
+            }
+            *last_eviction = now;
+        }
+
+        self.sessions.write().retain(|_, &mut (_, expires)| expires > now);
+    }
+}
+
+impl SessionStore for MemoryStore {
+    fn load(&self, id: &str) -> Result<Option<SessionRecord>, SessionError> {
+        Ok(self.sessions.read().get(id)
+            .filter(|&&(_, expires)| expires > Instant::now())
+            .map(|&(ref record, _)| record.clone()))
+    }
+
+    fn save(&self, id: &str, record: &SessionRecord, ttl: Duration) -> Result<(), SessionError> {
+        self.evict_expired();
+        self.sessions.write().insert(id.to_string(), (record.clone(), Instant::now() + ttl));
+        Ok(())
+    }
+
+    fn remove(&self, id: &str) -> Result<(), SessionError> {
+        self.sessions.write().remove(id);
+        Ok(())
+    }
+}
+
+struct SessionState {
+    id: Option<String>,
+    persisted: bool,
+    values: HashMap<String, String>,
+    created: SystemTime,
+    modified: bool,
+    regenerate: bool,
+    destroyed: bool,
+}
+
+/// The session of a request, inserted in the request state by the session middleware.
+/// # Example
+/// ```rust,no_run
+/// fn login(_ctx: &(), req: &SyncRequest, res: &mut SyncResponse) {
+///     if let Ok(session) = req.require_state::<Session>() {
+///         // The privilege changes, so the session id must change as well
+///         session.regenerate();
+///         session.insert("user_id", 42);
+///         res.status(StatusCode::NO_CONTENT);
+///     }
+/// }
+/// ```
+pub struct Session {
+    state: Mutex<SessionState>,
+}
+
+impl Session {
+    fn new(id: Option<String>, record: Option<SessionRecord>) -> Self {
+        let persisted = record.is_some();
+        let (values, created) = match record {
+            Some(record) => (record.values, record.created),
+            None => (HashMap::new(), SystemTime::now()),
+        };
+
+        Session {
+            state: Mutex::new(SessionState {
+                id,
+                persisted,
+                values,
+                created,
+                modified: false,
+                regenerate: false,
+                destroyed: false,
+            }),
+        }
+    }
+
+    /// Returns the value of `key` parsed as a `T`, none if there is no such value or it does not parse
+    pub fn get<T: FromStr>(&self, key: &str) -> Option<T> {
+        self.state.lock().values.get(key).and_then(|v| v.parse::<T>().ok())
+    }
+
+    /// Returns whether the session has a value for `key`
+    pub fn contains(&self, key: &str) -> bool {
+        self.state.lock().values.contains_key(key)
+    }
+
+    /// Set the value of `key`, replacing the previous one
+    pub fn insert<T: ToString>(&self, key: &str, value: T) {
+        let mut state = self.state.lock();
+        state.values.insert(key.to_string(), value.to_string());
+        state.modified = true;
+    }
+
+    /// Remove the value of `key`, returning it if any
+    pub fn remove(&self, key: &str) -> Option<String> {
+        let mut state = self.state.lock();
+        state.modified = true;
+        state.values.remove(key)
+    }
+
+    /// Remove every value of the session
+    pub fn clear(&self) {
+        let mut state = self.state.lock();
+        state.values.clear();
+        state.modified = true;
+    }
+
+    /// Issue a new id for the session once the request is handled, keeping its values. This must be called when the
+    /// privilege of the session changes, e.g. on login, so a session id obtained beforehand becomes useless.
+    pub fn regenerate(&self) {
+        self.state.lock().regenerate = true;
+    }
+
+    /// Remove the session from the store and the client once the request is handled, e.g. on logout
+    pub fn destroy(&self) {
+        let mut state = self.state.lock();
+        state.values.clear();
+        state.destroyed = true;
+    }
+}
+
+/// A builder of the session middleware
+pub struct Builder {
+    store: Arc<SessionStore>,
+    cookie_name: String,
+    cookie_path: String,
+    cookie_domain: Option<String>,
+    secure: bool,
+    same_site: SameSite,
+    idle_timeout: Duration,
+    absolute_timeout: Duration,
+}
+
+impl Builder {
+    /// Create a new session middleware builder storing the sessions in `store`
+    pub fn new<S: 'static + SessionStore>(store: S) -> Self {
+        Builder {
+            store: Arc::new(store),
+            cookie_name: "saphir_session".to_string(),
+            cookie_path: "/".to_string(),
+            cookie_domain: None,
+            secure: false,
+            same_site: SameSite::Lax,
+            idle_timeout: Duration::from_secs(30 * 60),
+            absolute_timeout: Duration::from_secs(24 * 60 * 60),
+        }
+    }
+
+    /// Set the name of the session cookie, `saphir_session` by default
+    pub fn cookie_name(mut self, name: &str) -> Self {
+        self.cookie_name = name.to_string();
+        self
+    }
+
+    /// Set the path of the session cookie, `/` by default
+    pub fn cookie_path(mut self, path: &str) -> Self {
+        self.cookie_path = path.to_string();
+        self
+    }
+
+    /// Set the domain of the session cookie, which is otherwise only sent to the host which issued it
+    pub fn cookie_domain(mut self, domain: &str) -> Self {
+        self.cookie_domain = Some(domain.to_string());
+        self
+    }
+
+    /// Only send the session cookie over https
+    pub fn secure(mut self) -> Self {
+        self.secure = true;
+        self
+    }
+
+    /// Set the `SameSite` attribute of the session cookie, `Lax` by default
+    pub fn same_site(mut self, same_site: SameSite) -> Self {
+        self.same_site = same_site;
+        self
+    }
+
+    /// Expire the sessions unused for `timeout`, 30 minutes by default
+    pub fn idle_timeout(mut self, timeout: Duration) -> Self {
+        self.idle_timeout = timeout;
+        self
+    }
+
+    /// Expire the sessions `timeout` after they were created, whatever their use or the rotations of their id, 24 hours
+    /// by default
+    pub fn absolute_timeout(mut self, timeout: Duration) -> Self {
+        self.absolute_timeout = timeout;
+        self
+    }
+
+    /// Build the session middleware
+    pub fn build(self) -> Sessions {
+        let Builder {
+            store,
+            cookie_name,
+            cookie_path,
+            cookie_domain,
+            secure,
+            same_site,
+            idle_timeout,
+            absolute_timeout,
+        } = self;
+
+        Sessions {
+            store,
+            cookie_name,
+            cookie_path,
+            cookie_domain,
+            secure,
+            same_site,
+            idle_timeout,
+            absolute_timeout,
+            random: SystemRandom::new(),
+        }
+    }
+}
+
+/// A middleware loading the session of a request from its session cookie, and saving it once the request is handled.
+/// The session is available to the following middlewares and the handlers as the `Session` request state. A session
+/// cookie is only issued once a value is inserted in the session.
+/// # Example
+/// ```rust,no_run
+/// let sessions = Sessions::builder(MemoryStore::new())
+///     .secure()
+///     .idle_timeout(Duration::from_secs(15 * 60))
+///     .absolute_timeout(Duration::from_secs(8 * 60 * 60))
+///     .build();
+///
+/// let middlewares = middleware::Builder::new().apply(sessions, vec!["/"], None).build();
+/// ```
+pub struct Sessions {
+    store: Arc<SessionStore>,
+    cookie_name: String,
+    cookie_path: String,
+    cookie_domain: Option<String>,
+    secure: bool,
+    same_site: SameSite,
+    idle_timeout: Duration,
+    absolute_timeout: Duration,
+    random: SystemRandom,
+}
+
+impl Sessions {
+    /// Create a new session middleware builder storing the sessions in `store`
+    pub fn builder<S: 'static + SessionStore>(store: S) -> Builder {
+        Builder::new(store)
+    }
+
+    fn generate_id(&self) -> Result<String, SessionError> {
+        let mut bytes = [0u8; 32];
+        self.random.fill(&mut bytes).map_err(|_| SessionError::Store("Unable to generate a session id".to_string()))?;
+
+        let mut id = String::with_capacity(64);
+        for byte in bytes.iter() {
+            let _ = write!(id, "{:02x}", byte);
+        }
+
+        Ok(id)
+    }
+
+    fn expired(&self, record: &SessionRecord, now: SystemTime) -> bool {
+        let elapsed = |since: SystemTime| now.duration_since(since).unwrap_or_else(|_| Duration::from_secs(0));
+        elapsed(record.last_access) >= self.idle_timeout || elapsed(record.created) >= self.absolute_timeout
+    }
+
+    fn cookie(&self, value: String) -> Cookie<'static> {
+        let mut cookie = Cookie::build(self.cookie_name.clone(), value)
+            .path(self.cookie_path.clone())
+            .http_only(true)
+            .secure(self.secure)
+            .same_site(self.same_site)
+            .finish();
+
+        if let Some(ref domain) = self.cookie_domain {
+            cookie.set_domain(domain.clone());
+        }
+
+        cookie
+    }
+
+    fn load(&self, req: &SyncRequest) -> Result<Session, SessionError> {
+        let id = match req.cookies().get(&self.cookie_name) {
+            Some(cookie) => cookie.value().to_string(),
+            None => return Ok(Session::new(None, None)),
+        };
+
+        match self.store.load(&id)? {
+            Some(ref record) if self.expired(record, SystemTime::now()) => {
+                self.store.remove(&id)?;
+                Ok(Session::new(Some(id), None))
+            }
+            Some(record) => Ok(Session::new(Some(id), Some(record))),
+            None => Ok(Session::new(Some(id), None)),
+        }
+    }
+
+    fn save(&self, session: Session, res: &mut SyncResponse) -> Result<(), SessionError> {
+        let SessionState { id, persisted, values, created, modified, regenerate, destroyed } = session.state.into_inner();
+
+        if destroyed || (values.is_empty() && modified) {
+            if let Some(id) = id {
+                self.store.remove(&id)?;
+                res.remove_cookie(self.cookie(String::new()));
+            }
+            return Ok(());
+        }
+
+        if values.is_empty() {
+            // An unknown or expired session id is never adopted, a new one is issued once a value is inserted
+            return Ok(());
+        }
+
+        let now = SystemTime::now();
+        // The creation time survives an id rotation, so rotating never extends the absolute expiry
+        let session_id = match id {
+            Some(ref id) if persisted && !regenerate => id.clone(),
+            _ => {
+                if let (true, Some(ref id)) = (persisted, &id) {
+                    self.store.remove(id)?;
+                }
+                let new_id = self.generate_id()?;
+                res.cookie(self.cookie(new_id.clone()));
+                new_id
+            }
+        };
+
+        let record = SessionRecord { values, created, last_access: now };
+        let remaining = self.absolute_timeout.checked_sub(now.duration_since(created).unwrap_or_else(|_| Duration::from_secs(0)))
+            .unwrap_or_else(|| Duration::from_secs(0));
+
+        self.store.save(&session_id, &record, self.idle_timeout.min(remaining))
+    }
+}
+
+impl Middleware for Sessions {
+    fn resolve(&self, req: &mut SyncRequest, res: &mut SyncResponse) -> RequestContinuation {
+        match self.load(req) {
+            Ok(session) => {
+                req.insert_state(session);
+                RequestContinuation::Continue
+            }
+            Err(e) => {
+                error!("Unable to load the session: {}", e);
+                res.status(StatusCode::INTERNAL_SERVER_ERROR);
+                RequestContinuation::Stop
+            }
+        }
+    }
+
+    fn after(&self, req: &mut SyncRequest, res: &mut SyncResponse) {
+        if let Some(session) = req.remove_state::<Session>() {
+            if let Err(e) = self.save(session, res) {
+                error!("Unable to save the session: {}", e);
+                res.status(StatusCode::INTERNAL_SERVER_ERROR);
+            }
+        }
+    }
+}
@@ -0,0 +1,712 @@
+use std::collections::hash_map::DefaultHasher;
+use std::fs::{self, File};
+use std::hash::{Hash, Hasher};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use hashbrown::HashMap;
+use log::error;
+
+use crate::controller::{Controller, RouteInfo, RouteSegment};
+use crate::http::*;
+use crate::http::header::Header;
+use crate::utils::preferred_encoding;
+
+///
+pub struct Builder {
+    base_path: String,
+    root: PathBuf,
+    index_files: Vec<String>,
+    spa_fallback: Option<String>,
+    hidden_files: bool,
+}
+
+impl Builder {
+    /// Create a new builder of a controller serving the files of the `root` directory under `base_path`
+    pub fn new<P: AsRef<Path>>(base_path: &str, root: P) -> Self {
+        Builder {
+            base_path: base_path.to_string(),
+            root: root.as_ref().to_path_buf(),
+            index_files: vec!["index.html".to_string()],
+            spa_fallback: None,
+            hidden_files: false,
+        }
+    }
+
+    /// Set the files served when a directory is requested, tried in order. Defaults to `index.html`.
+    pub fn index_files(mut self, index_files: &[&str]) -> Self {
+        self.index_files = index_files.iter().map(|f| f.to_string()).collect();
+        self
+    }
+
+    /// Serve the `file`, relative to the root directory, instead of answering `404 Not Found`, for single page
+    /// applications routing on the client side. Requests whose last segment has an extension, e.g. a missing script,
+    /// are still answered with `404 Not Found`.
+    pub fn spa_fallback(mut self, file: &str) -> Self {
+        self.spa_fallback = Some(file.trim_start_matches('/').to_string());
+        self
+    }
+
+    /// Serve the files and directories whose name starts with a dot, which are not found by default
+    pub fn hidden_files(mut self) -> Self {
+        self.hidden_files = true;
+        self
+    }
+
+    /// Build the controller, failing if the root directory cannot be resolved
+    pub fn build(self) -> io::Result<StaticFilesController> {
+        let Builder {
+            base_path,
+            root,
+            index_files,
+            spa_fallback,
+            hidden_files,
+        } = self;
+
+        let root = root.canonicalize()?;
+        if !root.is_dir() {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("{} is not a directory", root.display())));
+        }
+
+        Ok(StaticFilesController {
+            base_path,
+            root,
+            index_files,
+            spa_fallback,
+            hidden_files,
+        })
+    }
+}
+
+/// A controller serving the files of a directory on `GET` and `HEAD` requests, with the media type guessed from the
+/// file extension, conditional requests through `ETag` and `Last-Modified`, and single byte range requests.
+/// # Example
+/// ```rust,no_run
+/// let assets = StaticFilesController::builder("/assets", "./public")
+///     .spa_fallback("index.html")
+///     .build()
+///     .expect("Unable to serve the public directory");
+///
+/// let router = router::Builder::new().add(assets).build().expect("Invalid routes");
+/// ```
+pub struct StaticFilesController {
+    base_path: String,
+    root: PathBuf,
+    index_files: Vec<String>,
+    spa_fallback: Option<String>,
+    hidden_files: bool,
+}
+
+impl StaticFilesController {
+    /// Create a new static files controller builder
+    pub fn builder<P: AsRef<Path>>(base_path: &str, root: P) -> Builder {
+        Builder::new(base_path, root)
+    }
+
+    /// Resolve the requested path in the root directory, `None` if it could escape the root or is hidden
+    fn resolve(&self, segments: &[String]) -> Option<PathBuf> {
+        let mut path = self.root.clone();
+        for segment in segments {
+            if segment == ".." || segment == "." || segment.contains(|c| c == '\\' || c == '\0' || c == ':') {
+                return None;
+            }
+
+            if !self.hidden_files && segment.starts_with('.') {
+                return None;
+            }
+
+            path.push(segment);
+        }
+
+        // Symbolic links pointing outside of the root are not followed
+        match path.canonicalize() {
+            Ok(ref canonical) if canonical.starts_with(&self.root) => Some(canonical.clone()),
+            _ => None,
+        }
+    }
+
+    fn index_file(&self, dir: &Path) -> Option<PathBuf> {
+        self.index_files.iter().map(|f| dir.join(f)).find(|f| f.is_file())
+    }
+
+    fn fallback_file(&self, segments: &[String]) -> Option<PathBuf> {
+        let fallback = self.spa_fallback.as_ref()?;
+        if segments.last().map_or(false, |s| s.contains('.')) {
+            return None;
+        }
+
+        let segments: Vec<String> = fallback.split('/').map(|s| s.to_string()).collect();
+        self.resolve(&segments).filter(|f| f.is_file())
+    }
+
+    fn serve(&self, req: &SyncRequest, res: &mut SyncResponse, path: &Path) -> io::Result<()> {
+        let metadata = fs::metadata(path)?;
+        let representation = Representation {
+            media_type: media_type(path),
+            etag: file_entity_tag(metadata.len(), metadata.modified().ok()),
+            modified: metadata.modified().ok(),
+            encoding: None,
+            len: metadata.len(),
+        };
+
+        serve_representation(req, res, &representation, |start, len| {
+            let mut file = File::open(path)?;
+            file.seek(SeekFrom::Start(start))?;
+            let mut body = Vec::with_capacity(len as usize);
+            file.take(len).read_to_end(&mut body)?;
+            Ok(body)
+        })
+    }
+}
+
+impl Controller for StaticFilesController {
+    fn handle(&self, req: &mut SyncRequest, res: &mut SyncResponse) {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            res.status(StatusCode::METHOD_NOT_ALLOWED).header(header::ALLOW, "GET, HEAD");
+            return;
+        }
+
+        let segments: Vec<String> = req.current_path().iter().cloned().collect();
+        let file = match self.resolve(&segments) {
+            Some(ref path) if path.is_dir() => {
+                if !req.has_trailing_slash() && !segments.is_empty() {
+                    redirect_to_directory(req, res);
+                    return;
+                }
+
+                self.index_file(path)
+            }
+            Some(path) => Some(path),
+            None => None,
+        };
+
+        match file.or_else(|| self.fallback_file(&segments)) {
+            Some(path) => {
+                if let Err(e) = self.serve(req, res, &path) {
+                    error!("Unable to serve {}: {}", path.display(), e);
+                    *res = SyncResponse::new();
+                    res.status(StatusCode::INTERNAL_SERVER_ERROR);
+                }
+            }
+            None => {
+                res.status(StatusCode::NOT_FOUND);
+            }
+        }
+    }
+
+    fn base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    fn routes(&self) -> Vec<RouteInfo> {
+        file_routes(&self.base_path)
+    }
+}
+
+///
+pub struct EmbeddedAssetsBuilder {
+    base_path: String,
+    assets: Vec<(String, EmbeddedAsset)>,
+    index_files: Vec<String>,
+    spa_fallback: Option<String>,
+    last_modified: Option<SystemTime>,
+    misplaced_variant: bool,
+}
+
+impl EmbeddedAssetsBuilder {
+    /// Create a new builder of a controller serving embedded assets under `base_path`
+    pub fn new(base_path: &str) -> Self {
+        EmbeddedAssetsBuilder {
+            base_path: base_path.to_string(),
+            assets: Vec::new(),
+            index_files: vec!["index.html".to_string()],
+            spa_fallback: None,
+            last_modified: None,
+            misplaced_variant: false,
+        }
+    }
+
+    /// Add an asset served at `path`, relative to the base path, with the media type guessed from its extension
+    pub fn asset(mut self, path: &str, content: &'static [u8]) -> Self {
+        let path = path.trim_matches('/').to_string();
+        let media_type = media_type(Path::new(&path));
+        self.assets.push((path, EmbeddedAsset {
+            media_type,
+            identity: EmbeddedVariant::new(content, None),
+            gzip: None,
+            brotli: None,
+        }));
+        self
+    }
+
+    /// Set the media type of the last added asset
+    pub fn media_type(mut self, media_type: &str) -> Self {
+        match self.assets.last_mut() {
+            Some(&mut (_, ref mut asset)) => asset.media_type = media_type.to_string(),
+            None => self.misplaced_variant = true,
+        }
+        self
+    }
+
+    /// Add the gzip compressed variant of the last added asset, served to the clients accepting it
+    pub fn gzip(mut self, content: &'static [u8]) -> Self {
+        match self.assets.last_mut() {
+            Some(&mut (_, ref mut asset)) => asset.gzip = Some(EmbeddedVariant::new(content, Some("gzip"))),
+            None => self.misplaced_variant = true,
+        }
+        self
+    }
+
+    /// Add the brotli compressed variant of the last added asset, served to the clients accepting it, in preference to
+    /// the gzip one
+    pub fn brotli(mut self, content: &'static [u8]) -> Self {
+        match self.assets.last_mut() {
+            Some(&mut (_, ref mut asset)) => asset.brotli = Some(EmbeddedVariant::new(content, Some("br"))),
+            None => self.misplaced_variant = true,
+        }
+        self
+    }
+
+    /// Set the assets served when a directory is requested, tried in order. Defaults to `index.html`.
+    pub fn index_files(mut self, index_files: &[&str]) -> Self {
+        self.index_files = index_files.iter().map(|f| f.to_string()).collect();
+        self
+    }
+
+    /// Serve the asset at `path` instead of answering `404 Not Found`, see `static_files::Builder::spa_fallback`
+    pub fn spa_fallback(mut self, path: &str) -> Self {
+        self.spa_fallback = Some(path.trim_matches('/').to_string());
+        self
+    }
+
+    /// Set the `Last-Modified` date of every asset, e.g. the build date of the binary
+    pub fn last_modified(mut self, last_modified: SystemTime) -> Self {
+        self.last_modified = Some(last_modified);
+        self
+    }
+
+    /// Build the controller, failing if a variant or a media type was set before adding any asset, or if an asset or
+    /// the fallback is missing
+    pub fn build(self) -> io::Result<EmbeddedAssetsController> {
+        let EmbeddedAssetsBuilder {
+            base_path,
+            assets,
+            index_files,
+            spa_fallback,
+            last_modified,
+            misplaced_variant,
+        } = self;
+
+        if misplaced_variant {
+            return Err(io::Error::new(io::ErrorKind::InvalidInput, "An asset variant was set before adding any asset"));
+        }
+
+        let mut map = HashMap::new();
+        for (path, asset) in assets {
+            if map.contains_key(&path) {
+                return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The asset {} is added twice", path)));
+            }
+            map.insert(path, asset);
+        }
+
+        if let Some(ref fallback) = spa_fallback {
+            if !map.contains_key(fallback) {
+                return Err(io::Error::new(io::ErrorKind::NotFound, format!("The fallback asset {} is not embedded", fallback)));
+            }
+        }
+
+        Ok(EmbeddedAssetsController {
+            base_path,
+            assets: map,
+            index_files,
+            spa_fallback,
+            last_modified,
+        })
+    }
+}
+
+struct EmbeddedAsset {
+    media_type: String,
+    identity: EmbeddedVariant,
+    gzip: Option<EmbeddedVariant>,
+    brotli: Option<EmbeddedVariant>,
+}
+
+struct EmbeddedVariant {
+    content: &'static [u8],
+    etag: header::EntityTag,
+    encoding: Option<&'static str>,
+}
+
+impl EmbeddedVariant {
+    fn new(content: &'static [u8], encoding: Option<&'static str>) -> Self {
+        let mut hasher = DefaultHasher::new();
+        content.hash(&mut hasher);
+        let tag = match encoding {
+            Some(encoding) => format!("{:x}-{:x}-{}", content.len(), hasher.finish(), encoding),
+            None => format!("{:x}-{:x}", content.len(), hasher.finish()),
+        };
+
+        EmbeddedVariant {
+            content,
+            etag: header::EntityTag::strong(tag),
+            encoding,
+        }
+    }
+}
+
+impl EmbeddedAsset {
+    /// Select the variant served to a client from its `Accept-Encoding` header, brotli being preferred on equal quality
+    fn variant(&self, req: &SyncRequest) -> &EmbeddedVariant {
+        if self.gzip.is_none() && self.brotli.is_none() {
+            return &self.identity;
+        }
+
+        let accepted = header::AcceptEncoding::parse_header(&req.headers_map().get_all(header::ACCEPT_ENCODING))
+            .map(|a| a.0)
+            .unwrap_or_default();
+
+        let mut candidates = Vec::new();
+        if let Some(ref brotli) = self.brotli {
+            candidates.push((brotli, header::Encoding::Brotli));
+        }
+        if let Some(ref gzip) = self.gzip {
+            candidates.push((gzip, header::Encoding::Gzip));
+        }
+
+        preferred_encoding(&accepted, &candidates).unwrap_or(&self.identity)
+    }
+}
+
+/// A controller serving assets embedded in the binary, e.g. with `include_bytes!`, with the same caching semantics as
+/// the `StaticFilesController`. Precompressed variants of the assets are served to the clients accepting them.
+/// The assets of a directory can be embedded by a build script with `static_files::generate_embedded_assets`.
+/// # Example
+/// ```rust,no_run
+/// let assets = EmbeddedAssetsController::builder("/")
+///     .asset("index.html", include_bytes!("../public/index.html"))
+///     .asset("app.js", include_bytes!("../public/app.js"))
+///     .gzip(include_bytes!("../public/app.js.gz"))
+///     .spa_fallback("index.html")
+///     .build()
+///     .expect("Invalid embedded assets");
+/// ```
+pub struct EmbeddedAssetsController {
+    base_path: String,
+    assets: HashMap<String, EmbeddedAsset>,
+    index_files: Vec<String>,
+    spa_fallback: Option<String>,
+    last_modified: Option<SystemTime>,
+}
+
+impl EmbeddedAssetsController {
+    /// Create a new embedded assets controller builder
+    pub fn builder(base_path: &str) -> EmbeddedAssetsBuilder {
+        EmbeddedAssetsBuilder::new(base_path)
+    }
+
+    fn index_asset(&self, dir: &str) -> Option<&EmbeddedAsset> {
+        self.index_files.iter()
+            .map(|f| if dir.is_empty() { f.clone() } else { format!("{}/{}", dir, f) })
+            .filter_map(|p| self.assets.get(&p))
+            .next()
+    }
+}
+
+impl Controller for EmbeddedAssetsController {
+    fn handle(&self, req: &mut SyncRequest, res: &mut SyncResponse) {
+        if req.method() != Method::GET && req.method() != Method::HEAD {
+            res.status(StatusCode::METHOD_NOT_ALLOWED).header(header::ALLOW, "GET, HEAD");
+            return;
+        }
+
+        let segments: Vec<String> = req.current_path().iter().cloned().collect();
+        let path = segments.join("/");
+        let asset = match self.assets.get(&path) {
+            Some(asset) => Some(asset),
+            None => match self.index_asset(&path) {
+                Some(_) if !req.has_trailing_slash() && !segments.is_empty() => {
+                    redirect_to_directory(req, res);
+                    return;
+                }
+                index => index,
+            },
+        };
+
+        let fallback = || {
+            if segments.last().map_or(false, |s| s.contains('.')) {
+                return None;
+            }
+            self.spa_fallback.as_ref().and_then(|f| self.assets.get(f))
+        };
+
+        match asset.or_else(fallback) {
+            Some(asset) => {
+                let variant = asset.variant(req);
+                let representation = Representation {
+                    media_type: asset.media_type.clone(),
+                    etag: variant.etag.clone(),
+                    modified: self.last_modified,
+                    encoding: variant.encoding,
+                    len: variant.content.len() as u64,
+                };
+
+                if asset.gzip.is_some() || asset.brotli.is_some() {
+                    res.header(header::VARY, "Accept-Encoding");
+                }
+
+                let _ = serve_representation(req, res, &representation, |start, len| {
+                    Ok(variant.content[start as usize..(start + len) as usize].to_vec())
+                });
+            }
+            None => {
+                res.status(StatusCode::NOT_FOUND);
+            }
+        }
+    }
+
+    fn base_path(&self) -> &str {
+        &self.base_path
+    }
+
+    fn routes(&self) -> Vec<RouteInfo> {
+        file_routes(&self.base_path)
+    }
+}
+
+/// Write the rust source of a function adding every file of the `dir` directory, recursively, to an embedded assets
+/// builder. Files with a `.gz` or a `.br` extension are added as the precompressed variants of the file without it.
+/// Meant to be called from a build script, the generated function being included in the crate.
+/// # Example
+/// ```rust,no_run
+/// // build.rs
+/// let out = std::path::Path::new(&std::env::var("OUT_DIR").unwrap()).join("assets.rs");
+/// saphir::static_files::generate_embedded_assets("public", &out).unwrap();
+///
+/// // main.rs
+/// include!(concat!(env!("OUT_DIR"), "/assets.rs"));
+/// let assets = embedded_assets(EmbeddedAssetsController::builder("/")).build().unwrap();
+/// ```
+pub fn generate_embedded_assets<P: AsRef<Path>, Q: AsRef<Path>>(dir: P, out: Q) -> io::Result<()> {
+    fn collect(root: &Path, dir: &Path, files: &mut Vec<(String, PathBuf)>) -> io::Result<()> {
+        for entry in fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.is_dir() {
+                collect(root, &path, files)?;
+            } else {
+                let relative = path.strip_prefix(root).map_err(|e| io::Error::new(io::ErrorKind::Other, e.to_string()))?;
+                let segments: Vec<String> = relative.components().map(|c| c.as_os_str().to_string_lossy().into_owned()).collect();
+                files.push((segments.join("/"), path.canonicalize()?));
+            }
+        }
+        Ok(())
+    }
+
+    let root = dir.as_ref().canonicalize()?;
+    let mut files = Vec::new();
+    collect(&root, &root, &mut files)?;
+    files.sort();
+
+    let variant = |name: &str, extension: &str| files.iter().find(|&&(ref n, _)| *n == format!("{}.{}", name, extension)).map(|&(_, ref p)| p.clone());
+    let mut source = String::from("/// Add the embedded assets to the builder\n#[allow(dead_code)]\npub fn embedded_assets(builder: saphir::static_files::EmbeddedAssetsBuilder) -> saphir::static_files::EmbeddedAssetsBuilder {\n    builder\n");
+    for &(ref name, ref path) in &files {
+        let is_variant = |extension: &str| name.ends_with(extension) && files.iter().any(|&(ref n, _)| *n == name[..name.len() - extension.len()]);
+        if is_variant(".gz") || is_variant(".br") {
+            continue;
+        }
+
+        source.push_str(&format!("        .asset({:?}, include_bytes!({:?}))\n", name, path.display().to_string()));
+        if let Some(gzip) = variant(name, "gz") {
+            source.push_str(&format!("        .gzip(include_bytes!({:?}))\n", gzip.display().to_string()));
+        }
+        if let Some(brotli) = variant(name, "br") {
+            source.push_str(&format!("        .brotli(include_bytes!({:?}))\n", brotli.display().to_string()));
+        }
+    }
+    source.push_str("}\n");
+
+    fs::write(out, source)
+}
+
+/// Redirect a request for a directory to the same path with a trailing slash, as relative links of an index file only
+/// resolve against the directory with it
+fn redirect_to_directory(req: &SyncRequest, res: &mut SyncResponse) {
+    let mut location = format!("{}/", req.uri().path());
+    if let Some(query) = req.uri().query() {
+        location.push('?');
+        location.push_str(query);
+    }
+    res.status(StatusCode::PERMANENT_REDIRECT).header(header::LOCATION, location.as_str());
+}
+
+/// The routes of a controller serving files under its base path
+fn file_routes(base_path: &str) -> Vec<RouteInfo> {
+    vec![Method::GET, Method::HEAD].into_iter().map(|method| RouteInfo {
+        method,
+        path: "/<path..>".to_string(),
+        segments: vec![RouteSegment::Tail { name: Some("path".to_string()) }],
+        guards: Vec::new(),
+        controller: base_path.to_string(),
+        name: None,
+        doc: None,
+        trailing_slash: false,
+        case_insensitive: false,
+        consumes: Vec::new(),
+        produces: Vec::new(),
+        version: None,
+        host: None,
+    }).collect()
+}
+
+/// The metadata of a file or an embedded asset, in one of its encodings
+struct Representation {
+    media_type: String,
+    etag: header::EntityTag,
+    modified: Option<SystemTime>,
+    encoding: Option<&'static str>,
+    len: u64,
+}
+
+/// Answer a `GET` or `HEAD` request with a representation, honoring conditional and range requests. The body, or the
+/// requested range of it, is read from its start and length.
+fn serve_representation<F>(req: &SyncRequest, res: &mut SyncResponse, representation: &Representation, read: F) -> io::Result<()>
+    where F: FnOnce(u64, u64) -> io::Result<Vec<u8>> {
+    let Representation { ref media_type, ref etag, modified, encoding, len } = *representation;
+
+    // Http dates only have a precision of a second
+    let modified = modified.map(|m| {
+        let secs = m.duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0);
+        UNIX_EPOCH + ::std::time::Duration::from_secs(secs)
+    });
+
+    res.header(header::CONTENT_TYPE, media_type.as_str())
+        .header(header::ACCEPT_RANGES, "bytes")
+        .header(header::ETAG, etag.to_string().as_str());
+    if let Some(modified) = modified {
+        res.header(header::LAST_MODIFIED, header::HttpDate::from(modified).to_string().as_str());
+    }
+    if let Some(encoding) = encoding {
+        res.header(header::CONTENT_ENCODING, encoding);
+    }
+
+    if not_modified(req, etag, modified) {
+        res.status(StatusCode::NOT_MODIFIED);
+        return Ok(());
+    }
+
+    let range = match requested_range(req, etag, modified) {
+        Some(spec) => match spec.to_satisfiable_range(len) {
+            Some(range) => Some(range),
+            None => {
+                res.status(StatusCode::RANGE_NOT_SATISFIABLE)
+                    .header(header::CONTENT_RANGE, format!("bytes */{}", len).as_str());
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+
+    let (start, end) = range.unwrap_or((0, len.saturating_sub(1)));
+    let content_len = if len == 0 { 0 } else { end - start + 1 };
+    if range.is_some() {
+        res.status(StatusCode::PARTIAL_CONTENT)
+            .header(header::CONTENT_RANGE, format!("bytes {}-{}/{}", start, end, len).as_str());
+    } else {
+        res.status(StatusCode::OK);
+    }
+
+    if req.method() == Method::HEAD {
+        res.header(header::CONTENT_LENGTH, content_len.to_string().as_str());
+        return Ok(());
+    }
+
+    res.body(read(start, content_len)?);
+    Ok(())
+}
+
+/// A strong entity tag derived from the size and the modification time of a file
+fn file_entity_tag(len: u64, modified: Option<SystemTime>) -> header::EntityTag {
+    let nanos = modified.and_then(|m| m.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs() * 1_000_000_000 + u64::from(d.subsec_nanos()))
+        .unwrap_or(0);
+    header::EntityTag::strong(format!("{:x}-{:x}", len, nanos))
+}
+
+/// Whether the client copy is still fresh according to `If-None-Match`, or `If-Modified-Since` in its absence
+fn not_modified(req: &SyncRequest, etag: &header::EntityTag, modified: Option<SystemTime>) -> bool {
+    let headers = req.headers_map();
+    if headers.contains_key(header::IF_NONE_MATCH) {
+        return match header::IfNoneMatch::parse_header(&headers.get_all(header::IF_NONE_MATCH)) {
+            Ok(header::IfNoneMatch::Any) => true,
+            Ok(header::IfNoneMatch::Items(ref tags)) => tags.iter().any(|t| t.weak_eq(etag)),
+            Err(_) => false,
+        };
+    }
+
+    match (header::IfModifiedSince::parse_header(&headers.get_all(header::IF_MODIFIED_SINCE)), modified) {
+        (Ok(header::IfModifiedSince(since)), Some(modified)) => modified <= SystemTime::from(since),
+        _ => false,
+    }
+}
+
+/// The single byte range requested, ignored when several ranges are requested or when `If-Range` does not match
+fn requested_range(req: &SyncRequest, etag: &header::EntityTag, modified: Option<SystemTime>) -> Option<header::ByteRangeSpec> {
+    let headers = req.headers_map();
+    if !headers.contains_key(header::RANGE) {
+        return None;
+    }
+
+    if headers.contains_key(header::IF_RANGE) {
+        let fresh = match header::IfRange::parse_header(&headers.get_all(header::IF_RANGE)) {
+            Ok(header::IfRange::EntityTag(ref tag)) => tag.strong_eq(etag),
+            Ok(header::IfRange::Date(date)) => modified.map_or(false, |m| m == SystemTime::from(date)),
+            Err(_) => false,
+        };
+
+        if !fresh {
+            return None;
+        }
+    }
+
+    match header::Range::parse_header(&headers.get_all(header::RANGE)) {
+        Ok(header::Range::Bytes(mut ranges)) if ranges.len() == 1 => ranges.pop(),
+        _ => None,
+    }
+}
+
+/// Guess the media type of a file from its extension
+pub(crate) fn media_type(path: &Path) -> String {
+    let extension = path.extension().and_then(|e| e.to_str()).map(|e| e.to_lowercase()).unwrap_or_default();
+    let mime = match extension.as_str() {
+        "html" | "htm" => header::mime::TEXT_HTML_UTF_8,
+        "css" => header::mime::TEXT_CSS_UTF_8,
+        "js" | "mjs" => header::mime::APPLICATION_JAVASCRIPT_UTF_8,
+        "json" | "map" => header::mime::APPLICATION_JSON,
+        "txt" | "md" => header::mime::TEXT_PLAIN_UTF_8,
+        "csv" => header::mime::TEXT_CSV_UTF_8,
+        "xml" => header::mime::TEXT_XML,
+        "svg" => header::mime::IMAGE_SVG,
+        "png" => header::mime::IMAGE_PNG,
+        "jpg" | "jpeg" => header::mime::IMAGE_JPEG,
+        "gif" => header::mime::IMAGE_GIF,
+        "bmp" => header::mime::IMAGE_BMP,
+        "woff" => header::mime::FONT_WOFF,
+        "woff2" => header::mime::FONT_WOFF2,
+        "pdf" => header::mime::APPLICATION_PDF,
+        "webp" => return "image/webp".to_string(),
+        "ico" => return "image/x-icon".to_string(),
+        "ttf" => return "font/ttf".to_string(),
+        "otf" => return "font/otf".to_string(),
+        "wasm" => return "application/wasm".to_string(),
+        "mp4" => return "video/mp4".to_string(),
+        "webm" => return "video/webm".to_string(),
+        "mp3" => return "audio/mpeg".to_string(),
+        "wav" => return "audio/wav".to_string(),
+        "zip" => return "application/zip".to_string(),
+        "gz" => return "application/gzip".to_string(),
+        _ => header::mime::APPLICATION_OCTET_STREAM,
+    };
+
+    mime.to_string()
+}
@@ -1,11 +1,17 @@
 #![allow(dead_code)]
 
 use regex::Regex;
+use std::collections::VecDeque;
 use std::slice::Iter;
+use std::fmt;
+use saphir_regex_flags::regex_with_flags;
+use crate::http::header;
 
 #[derive(Debug)]
 pub(crate) struct UriPathMatcher {
-    inner: Vec<UriPathSegmentMatcher>
+    inner: Vec<UriPathSegmentMatcher>,
+    trailing_slash: bool,
+    case_insensitive: bool,
 }
 
 impl UriPathMatcher {
@@ -26,10 +32,13 @@ impl UriPathMatcher {
             return Err(err.remove(0).err().expect("This is never gonna happens"));
         }
 
-        let inner = ok.into_iter().map(|res| res.unwrap()).collect();
+        let inner: Vec<UriPathSegmentMatcher> = ok.into_iter().map(|res| res.unwrap()).collect();
+        validate_segments(&inner)?;
 
         Ok(UriPathMatcher {
-            inner
+            inner,
+            trailing_slash: path_str.len() > 1 && path_str.ends_with('/'),
+            case_insensitive: false,
         })
     }
 
@@ -50,7 +59,20 @@ impl UriPathMatcher {
             return Err(err.remove(0).err().expect("This is never gonna happens"));
         }
 
-        self.inner.extend(ok.into_iter().map(|res| res.unwrap()));
+        if self.has_tail() {
+            return Err("Unable to append to a path ending with a tail segment".to_string());
+        }
+
+        let appended: Vec<UriPathSegmentMatcher> = ok.into_iter().map(|res| res.unwrap()).collect();
+        if !appended.is_empty() {
+            if self.inner.last().map(|s| s.is_optional()).unwrap_or(false) {
+                return Err("Unable to append to a path ending with an optional segment".to_string());
+            }
+
+            validate_segments(&appended)?;
+            self.inner.extend(appended);
+            self.trailing_slash = append.len() > 1 && append.ends_with('/');
+        }
 
         Ok(())
     }
@@ -59,18 +81,90 @@ impl UriPathMatcher {
         let mut path_split = path.trim_start_matches('/').split('/');
 
         for segment in &self.inner {
-            if let Some(ref s) = path_split.next() {
-                if !segment.matches(s) {
-                    return false;
-                }
-            } else {
-                return false;
+            if segment.is_tail() {
+                return true;
+            }
+
+            match path_split.next() {
+                Some(ref s) if segment.matches(s, self.case_insensitive) => {}
+                // Optional segments being the last ones, the rest of the path is not part of the route
+                _ if segment.is_optional() => return true,
+                _ => return false,
             }
         }
 
         true
     }
 
+    /// Match the start of the `path` segments, returning the number of segments matched.
+    /// A tail segment matches every remaining segment, an optional segment only matches when present and valid.
+    /// Static segments are compared regardless of case when either the matcher or `case_insensitive` asks for it.
+    pub fn match_segments(&self, path: &VecDeque<String>, case_insensitive: bool) -> Option<usize> {
+        let case_insensitive = case_insensitive || self.case_insensitive;
+        let mut matched = 0;
+
+        for segment in &self.inner {
+            if segment.is_tail() {
+                return Some(path.len());
+            }
+
+            match path.get(matched) {
+                Some(s) if segment.matches(s, case_insensitive) => matched += 1,
+                _ if segment.is_optional() => return Some(matched),
+                _ => return None,
+            }
+        }
+
+        Some(matched)
+    }
+
+    /// Whether the path pattern ends with a slash, e.g. `/users/`
+    pub fn trailing_slash(&self) -> bool {
+        self.trailing_slash
+    }
+
+    /// Whether static segments are matched regardless of case
+    pub fn case_insensitive(&self) -> bool {
+        self.case_insensitive
+    }
+
+    pub fn set_case_insensitive(&mut self, case_insensitive: bool) {
+        self.case_insensitive = case_insensitive;
+    }
+
+    pub fn set_trailing_slash(&mut self, trailing_slash: bool) {
+        self.trailing_slash = trailing_slash;
+    }
+
+    /// Whether the last segment captures the rest of the path
+    pub fn has_tail(&self) -> bool {
+        self.inner.last().map(|s| s.is_tail()).unwrap_or(false)
+    }
+
+    /// Number of segments a path must have to be matched, optional and tail segments excluded
+    pub fn required_len(&self) -> usize {
+        self.inner.iter().filter(|s| !s.is_optional() && !s.is_tail()).count()
+    }
+
+    /// Whether both matchers would match some of the same paths
+    pub fn conflicts_with(&self, other: &UriPathMatcher) -> bool {
+        if self.trailing_slash != other.trailing_slash {
+            return false;
+        }
+
+        let case_insensitive = self.case_insensitive || other.case_insensitive;
+
+        if self.has_tail() || other.has_tail() {
+            return self.len() == other.len() && self.iter().zip(other.iter()).all(|(a, b)| a.conflicts_with(b, case_insensitive));
+        }
+
+        // Every length both matchers accept is compared, as optional segments can be omitted
+        let min = ::std::cmp::max(self.required_len(), other.required_len());
+        let max = ::std::cmp::min(self.len(), other.len());
+
+        (min..=max).any(|len| self.iter().take(len).zip(other.iter().take(len)).all(|(a, b)| a.conflicts_with(b, case_insensitive)))
+    }
+
     pub fn iter(&self) -> Iter<UriPathSegmentMatcher> {
         self.inner.iter()
     }
@@ -80,11 +174,51 @@ impl UriPathMatcher {
     }
 }
 
+impl fmt::Display for UriPathMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        if self.inner.is_empty() {
+            return write!(f, "/");
+        }
+
+        for segment in &self.inner {
+            write!(f, "/{}", segment)?;
+        }
+
+        if self.trailing_slash {
+            write!(f, "/")?;
+        }
+
+        Ok(())
+    }
+}
+
 #[derive(Debug)]
 pub(crate) enum UriPathSegmentMatcher {
     Static { segment: String },
     Variable { name: Option<String> },
     Custom { name: Option<String>, segment: Regex },
+    Tail { name: Option<String> },
+    Optional { segment: Box<UriPathSegmentMatcher> },
+}
+
+fn validate_segments(segments: &[UriPathSegmentMatcher]) -> Result<(), String> {
+    if let Some(pos) = segments.iter().position(|s| s.is_tail()) {
+        if pos + 1 < segments.len() {
+            return Err("A tail segment should be the last segment of a path".to_string());
+        }
+
+        if segments.iter().any(|s| s.is_optional()) {
+            return Err("A path ending with a tail segment cannot have optional segments".to_string());
+        }
+    }
+
+    if let Some(pos) = segments.iter().position(|s| s.is_optional()) {
+        if segments[pos..].iter().any(|s| !s.is_optional()) {
+            return Err("Optional segments should be the last segments of a path".to_string());
+        }
+    }
+
+    Ok(())
 }
 
 impl UriPathSegmentMatcher {
@@ -94,6 +228,15 @@ impl UriPathSegmentMatcher {
             return Err("A path segment should not contain any /".to_string());
         }
 
+        if segment.starts_with('<') && segment.ends_with(">?") {
+            let inner = UriPathSegmentMatcher::new(&segment[..segment.len() - 1])?;
+            if inner.is_tail() {
+                return Err("A tail segment cannot be optional".to_string());
+            }
+
+            return Ok(UriPathSegmentMatcher::Optional { segment: Box::new(inner) });
+        }
+
         if segment.starts_with('<') {
             if segment.ends_with('>') {
                 let s: Vec<&str> = segment.trim_start_matches('<').trim_end_matches('>').splitn(2, "#r").collect();
@@ -109,9 +252,20 @@ impl UriPathSegmentMatcher {
 
                 let name_c = name.clone();
 
+                if s.len() < 2 && s[0].ends_with("..") {
+                    let tail_name = s[0].trim_end_matches("..");
+                    let name = if tail_name.len() <= 1 {
+                        None
+                    } else {
+                        Some(tail_name.to_string())
+                    };
+
+                    return Ok(UriPathSegmentMatcher::Tail { name });
+                }
+
                 s.get(1).map(|r| {
-                    let r = r.trim_start_matches('(').trim_end_matches(')');
-                    Regex::new(r).map_err(|e| e.to_string()).map(|r| UriPathSegmentMatcher::Custom { name, segment: r })
+                    let r = regex_with_flags(r)?;
+                    Regex::new(&r).map_err(|e| e.to_string()).map(|r| UriPathSegmentMatcher::Custom { name, segment: r })
                 }).unwrap_or_else(|| Ok(UriPathSegmentMatcher::Variable { name: name_c }))
             } else {
                 Err("A variable path segment should start with < & end with >".to_string())
@@ -122,11 +276,14 @@ impl UriPathSegmentMatcher {
     }
 
     ///
-    pub fn matches(&self, other: &str) -> bool {
+    pub fn matches(&self, other: &str, case_insensitive: bool) -> bool {
         match self {
+            UriPathSegmentMatcher::Static { segment: ref s } if case_insensitive => s.to_lowercase() == other.to_lowercase(),
             UriPathSegmentMatcher::Static { segment: ref s } => s.eq(other),
             UriPathSegmentMatcher::Variable { name: ref _n } => true,
             UriPathSegmentMatcher::Custom { name: ref _n, segment: ref s } => s.is_match(other),
+            UriPathSegmentMatcher::Tail { name: ref _n } => true,
+            UriPathSegmentMatcher::Optional { segment: ref s } => s.matches(other, case_insensitive),
         }
     }
 
@@ -135,6 +292,41 @@ impl UriPathSegmentMatcher {
             UriPathSegmentMatcher::Static { segment: ref _s } => None,
             UriPathSegmentMatcher::Variable { name: ref n } => n.as_ref().map(|s| s.as_str()),
             UriPathSegmentMatcher::Custom { name: ref n, segment: ref _s } => n.as_ref().map(|s| s.as_str()),
+            UriPathSegmentMatcher::Tail { name: ref n } => n.as_ref().map(|s| s.as_str()),
+            UriPathSegmentMatcher::Optional { segment: ref s } => s.name(),
+        }
+    }
+
+    /// The segment matcher, without its optional wrapper
+    pub fn required(&self) -> &UriPathSegmentMatcher {
+        match self {
+            UriPathSegmentMatcher::Optional { segment: ref s } => s,
+            _ => self,
+        }
+    }
+
+    pub fn conflicts_with(&self, other: &UriPathSegmentMatcher, case_insensitive: bool) -> bool {
+        match (self.required(), other.required()) {
+            (UriPathSegmentMatcher::Static { segment: ref a }, UriPathSegmentMatcher::Static { segment: ref b }) if case_insensitive => a.to_lowercase() == b.to_lowercase(),
+            (UriPathSegmentMatcher::Static { segment: ref a }, UriPathSegmentMatcher::Static { segment: ref b }) => a.eq(b),
+            (UriPathSegmentMatcher::Variable { .. }, UriPathSegmentMatcher::Variable { .. }) => true,
+            (UriPathSegmentMatcher::Custom { segment: ref a, .. }, UriPathSegmentMatcher::Custom { segment: ref b, .. }) => a.as_str().eq(b.as_str()),
+            (UriPathSegmentMatcher::Tail { .. }, UriPathSegmentMatcher::Tail { .. }) => true,
+            _ => false,
+        }
+    }
+
+    pub fn is_tail(&self) -> bool {
+        match self {
+            UriPathSegmentMatcher::Tail { name: ref _n } => true,
+            _ => false
+        }
+    }
+
+    pub fn is_optional(&self) -> bool {
+        match self {
+            UriPathSegmentMatcher::Optional { segment: ref _s } => true,
+            _ => false
         }
     }
 
@@ -146,6 +338,87 @@ impl UriPathSegmentMatcher {
     }
 }
 
+impl fmt::Display for UriPathSegmentMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UriPathSegmentMatcher::Static { segment: ref s } => write!(f, "{}", s),
+            UriPathSegmentMatcher::Variable { name: ref n } => write!(f, "<{}>", n.as_ref().map(|s| s.as_str()).unwrap_or("_")),
+            UriPathSegmentMatcher::Custom { name: ref n, segment: ref s } => write!(f, "<{}#r({})>", n.as_ref().map(|s| s.as_str()).unwrap_or("_"), s.as_str()),
+            UriPathSegmentMatcher::Tail { name: ref n } => write!(f, "<{}..>", n.as_ref().map(|s| s.as_str()).unwrap_or("_")),
+            UriPathSegmentMatcher::Optional { segment: ref s } => write!(f, "{}?", s),
+        }
+    }
+}
+
+/// Matches a host name against a pattern of dot separated labels, e.g. `example.com`, `*.example.com` where the
+/// wildcard matches a single label, or `<tenant>.example.com` which captures the label. Hosts are matched regardless of case.
+#[derive(Debug, Clone)]
+pub(crate) struct HostMatcher {
+    pattern: String,
+    labels: Vec<HostLabel>,
+}
+
+#[derive(Debug, Clone)]
+enum HostLabel {
+    Static(String),
+    Wildcard,
+    Variable(String),
+}
+
+impl HostMatcher {
+    pub fn new(pattern: &str) -> Result<HostMatcher, String> {
+        let pattern = pattern.trim_end_matches('.').to_lowercase();
+        let labels = pattern.split('.').map(|label| {
+            if label.is_empty() {
+                Err(format!("The host pattern {} contains an empty label", pattern))
+            } else if label == "*" {
+                Ok(HostLabel::Wildcard)
+            } else if label.starts_with('<') && label.ends_with('>') && label.len() > 2 {
+                Ok(HostLabel::Variable(label[1..label.len() - 1].to_string()))
+            } else if label.contains(|c| c == '*' || c == '<' || c == '>') {
+                Err(format!("The host pattern label {} should either be a static label, `*` or `<name>`", label))
+            } else {
+                Ok(HostLabel::Static(label.to_string()))
+            }
+        }).collect::<Result<Vec<HostLabel>, String>>()?;
+
+        Ok(HostMatcher { pattern, labels })
+    }
+
+    /// Match a host, without its port, returning the captured labels
+    pub fn match_host(&self, host: &str) -> Option<Vec<(String, String)>> {
+        let host = host.trim_end_matches('.').to_lowercase();
+        let labels: Vec<&str> = host.split('.').collect();
+        if labels.len() != self.labels.len() {
+            return None;
+        }
+
+        let mut captures = Vec::new();
+        for (matcher, label) in self.labels.iter().zip(labels.into_iter()) {
+            match matcher {
+                HostLabel::Static(ref s) if s == label => {}
+                HostLabel::Static(_) => return None,
+                HostLabel::Wildcard if !label.is_empty() => {}
+                HostLabel::Variable(ref name) if !label.is_empty() => captures.push((name.clone(), label.to_string())),
+                _ => return None,
+            }
+        }
+
+        Some(captures)
+    }
+
+    /// The number of labels which are not static, exact hosts being tried before wildcard ones
+    pub fn wildcards(&self) -> usize {
+        self.labels.iter().filter(|l| match l { HostLabel::Static(_) => false, _ => true }).count()
+    }
+}
+
+impl fmt::Display for HostMatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.pattern)
+    }
+}
+
 /// Enum representing whether or not a request should continue to be processed be the server
 pub enum RequestContinuation {
     /// Next
@@ -192,6 +465,44 @@ impl ToRegex for ::regex::Regex {
     }
 }
 
+/// Escape a string to be embedded in a JSON string literal
+pub(crate) fn json_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+
+    for c in s.chars() {
+        match c {
+            '"' => escaped.push_str("\\\""),
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+            c => escaped.push(c),
+        }
+    }
+
+    escaped
+}
+
+/// The preferred content coding accepted by a client among `codings`, the first listed one on equal quality. A coding
+/// not named in `Accept-Encoding` gets the quality of the `*` wildcard, codings of quality zero are never selected.
+pub(crate) fn preferred_encoding<T: Copy>(accept_encoding: &[header::QualityItem<header::Encoding>], codings: &[(T, header::Encoding)]) -> Option<T> {
+    let wildcard = accept_encoding.iter()
+        .find(|item| item.item == header::Encoding::EncodingExt("*".to_string()))
+        .map(|item| item.quality);
+
+    codings.iter()
+        .filter_map(|&(coding, ref encoding)| {
+            let quality = accept_encoding.iter().find(|item| item.item == *encoding).map(|item| item.quality).or(wildcard)?;
+            if quality > header::q(0) { Some((coding, quality)) } else { None }
+        })
+        .fold(None, |best: Option<(T, header::Quality)>, (coding, quality)| match best {
+            Some((_, best_quality)) if best_quality >= quality => best,
+            _ => Some((coding, quality)),
+        })
+        .map(|(coding, _)| coding)
+}
+
 #[macro_export]
 /// Convert a str to a regex
 macro_rules! reg {